@@ -0,0 +1,577 @@
+use crate::output;
+use crate::png_chunks::{read_chunks, write_chunk, PNG_SIGNATURE};
+use image::gif::GifEncoder;
+use image::imageops::{self, FilterType};
+use image::png::PngEncoder;
+use image::{ColorType, Delay, DynamicImage, Frame, ImageFormat, RgbImage};
+use std::cell::RefCell;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+// how recorded frames are stored
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum AnimationFormat {
+    Gif,
+    Apng,
+    Frames,
+    Y4m,
+}
+
+// everything needed to record the growth of a painting
+#[derive(Clone, Debug)]
+pub struct AnimationOptions {
+    // every format is written at the same time
+    pub formats: Vec<AnimationFormat>,
+    // placed pixels between two frames
+    pub frame_interval: u64,
+    // frames wider than the canvas are not upscaled, the height keeps the aspect ratio
+    pub frame_width: Option<u32>,
+    pub frame_delay_ms: u32,
+    // how long the last frame is shown before an animation loops
+    pub final_hold_ms: u32,
+    // where the y4m stream goes, relative to the output directory, "-" streams to stdout
+    pub y4m_output: String,
+}
+
+impl AnimationFormat {
+    pub fn from_name(name: &str) -> Option<AnimationFormat> {
+        match name.to_lowercase().as_str() {
+            "gif" => Some(AnimationFormat::Gif),
+            "apng" => Some(AnimationFormat::Apng),
+            "frames" => Some(AnimationFormat::Frames),
+            "y4m" => Some(AnimationFormat::Y4m),
+            _ => None,
+        }
+    }
+}
+
+// a destination for animation frames, each frame is shown for delay_ms before the next one
+pub trait FrameSink {
+    fn add_frame(&mut self, frame: &RgbImage, delay_ms: u32) -> io::Result<()>;
+    // write whatever is still buffered, no frames are added afterwards
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+// captures a frame every frame_interval placed pixels, a frame is only handed to the sink
+// once the next one arrives so the last frame can be given the final hold duration
+pub struct AnimationRecorder {
+    options: AnimationOptions,
+    sinks: Vec<Box<dyn FrameSink>>,
+    pending_frame: Option<RgbImage>,
+    next_capture: u64,
+    last_capture: Option<u64>,
+}
+
+impl AnimationRecorder {
+    // a recorder writing into output_dir (growth.gif, growth.apng, frames/ and the y4m output)
+    pub fn new(options: AnimationOptions, output_dir: &Path) -> io::Result<AnimationRecorder> {
+        let mut sinks: Vec<Box<dyn FrameSink>> = Vec::new();
+        for format in options.formats.iter() {
+            sinks.push(match format {
+                AnimationFormat::Gif => Box::new(GifSink::new(output_dir.join("growth.gif"))),
+                AnimationFormat::Apng => Box::new(ApngSink::new(output_dir.join("growth.apng"))?),
+                AnimationFormat::Frames => {
+                    Box::new(FrameSequenceSink::new(output_dir.join("frames"))?)
+                }
+                AnimationFormat::Y4m => match options.y4m_output.as_str() {
+                    "-" => Box::new(Y4mSink::new(Box::new(io::stdout()), options.frame_delay_ms)),
+                    path => Box::new(Y4mSink::to_file(
+                        output_dir.join(path),
+                        options.frame_delay_ms,
+                    )?),
+                },
+            });
+        }
+        return Ok(AnimationRecorder::with_sinks(options, sinks));
+    }
+
+    pub fn with_sinks(
+        options: AnimationOptions,
+        sinks: Vec<Box<dyn FrameSink>>,
+    ) -> AnimationRecorder {
+        return AnimationRecorder {
+            options,
+            sinks,
+            pending_frame: None,
+            next_capture: 0,
+            last_capture: None,
+        };
+    }
+
+    // capture a frame if enough pixels were placed since the last one
+    pub fn capture(&mut self, image: &RgbImage, placed_count: u64) -> io::Result<()> {
+        if placed_count < self.next_capture {
+            return Ok(());
+        }
+        self.next_capture = placed_count + self.options.frame_interval;
+        self.last_capture = Some(placed_count);
+
+        let frame = self.scale_frame(image);
+        if let Some(previous) = self.pending_frame.replace(frame) {
+            for sink in self.sinks.iter_mut() {
+                sink.add_frame(&previous, self.options.frame_delay_ms)?;
+            }
+        }
+        return Ok(());
+    }
+
+    // capture the finished painting and hold it for final_hold_ms
+    pub fn finish(&mut self, image: &RgbImage, placed_count: u64) -> io::Result<()> {
+        if self.last_capture != Some(placed_count) {
+            self.next_capture = placed_count;
+            self.capture(image, placed_count)?;
+        }
+        let last_frame = self.pending_frame.take();
+        for sink in self.sinks.iter_mut() {
+            if let Some(last) = &last_frame {
+                sink.add_frame(last, self.options.final_hold_ms)?;
+            }
+            sink.finish()?;
+        }
+        return Ok(());
+    }
+
+    fn scale_frame(&self, image: &RgbImage) -> RgbImage {
+        match self.options.frame_width {
+            Some(frame_width) if frame_width < image.width() => {
+                let frame_height = ((image.height() as f64 * frame_width as f64
+                    / image.width() as f64)
+                    .round() as u32)
+                    .max(1);
+                return imageops::resize(image, frame_width, frame_height, FilterType::Triangle);
+            }
+            _ => return image.clone(),
+        }
+    }
+}
+
+fn to_io_error(error: image::ImageError) -> io::Error {
+    return io::Error::other(error.to_string());
+}
+
+// animated GIF, looping forever
+//
+// the image crate's encoder cannot write the looping extension, frames are encoded into memory
+// and the extension is inserted after the header once the animation is finished
+pub struct GifSink {
+    path: PathBuf,
+    buffer: SharedBuffer,
+    encoder: Option<GifEncoder<SharedBuffer>>,
+}
+
+// a byte buffer that stays readable while an encoder owns a handle to it
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(bytes);
+        return Ok(bytes.len());
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        return Ok(());
+    }
+}
+
+impl GifSink {
+    pub fn new(path: PathBuf) -> GifSink {
+        let buffer = SharedBuffer::default();
+        return GifSink {
+            path,
+            encoder: Some(GifEncoder::new(buffer.clone())),
+            buffer,
+        };
+    }
+}
+
+impl FrameSink for GifSink {
+    fn add_frame(&mut self, frame: &RgbImage, delay_ms: u32) -> io::Result<()> {
+        let encoder = match &mut self.encoder {
+            Some(encoder) => encoder,
+            None => return Ok(()),
+        };
+        let rgba = DynamicImage::ImageRgb8(frame.clone()).into_rgba();
+        return encoder
+            .encode_frame(Frame::from_parts(
+                rgba,
+                0,
+                0,
+                Delay::from_numer_denom_ms(delay_ms, 1),
+            ))
+            .map_err(to_io_error);
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        // dropping the encoder writes the trailer
+        self.encoder = None;
+        let encoded = self.buffer.0.borrow();
+        if encoded.len() < 13 {
+            return Ok(());
+        }
+
+        // the header is the 13 byte screen descriptor plus the optional global color table
+        let flags = encoded[10];
+        let mut header_length = 13;
+        if flags & 0x80 != 0 {
+            header_length += 3 << ((flags & 0x07) + 1);
+        }
+
+        // NETSCAPE2.0 application extension, 0 repetitions means loop forever
+        let loop_extension: [u8; 19] = [
+            0x21, 0xff, 0x0b, b'N', b'E', b'T', b'S', b'C', b'A', b'P', b'E', b'2', b'.', b'0',
+            0x03, 0x01, 0x00, 0x00, 0x00,
+        ];
+        return output::write_atomically(&self.path, |temporary_path| {
+            let mut file = BufWriter::new(File::create(temporary_path)?);
+            file.write_all(&encoded[..header_length])?;
+            file.write_all(&loop_extension)?;
+            file.write_all(&encoded[header_length..])?;
+            return file.flush();
+        });
+    }
+}
+
+// animated PNG, looping forever
+//
+// every frame is encoded as a regular PNG, its image data is moved into fcTL/fdAT chunks, the
+// frame count in acTL is filled in once the animation is finished, frames are written to the
+// temporary path and the file is only moved into place when it is complete
+pub struct ApngSink {
+    path: PathBuf,
+    file: BufWriter<File>,
+    frame_count: u32,
+    sequence_number: u32,
+    animation_control_offset: u64,
+}
+
+impl ApngSink {
+    pub fn new(path: PathBuf) -> io::Result<ApngSink> {
+        return Ok(ApngSink {
+            file: BufWriter::new(File::create(output::temporary_path(&path))?),
+            path,
+            frame_count: 0,
+            sequence_number: 0,
+            animation_control_offset: 0,
+        });
+    }
+
+    fn write_animation_control(&mut self) -> io::Result<()> {
+        let mut data: Vec<u8> = Vec::with_capacity(8);
+        data.extend_from_slice(&self.frame_count.to_be_bytes());
+        // 0 plays means loop forever
+        data.extend_from_slice(&0u32.to_be_bytes());
+        return write_chunk(&mut self.file, b"acTL", &data);
+    }
+}
+
+impl FrameSink for ApngSink {
+    fn add_frame(&mut self, frame: &RgbImage, delay_ms: u32) -> io::Result<()> {
+        let mut encoded: Vec<u8> = Vec::new();
+        PngEncoder::new(&mut encoded)
+            .encode(frame, frame.width(), frame.height(), ColorType::Rgb8)
+            .map_err(to_io_error)?;
+        let chunks = read_chunks(&encoded)?;
+
+        // the first frame also provides the header and doubles as the default image
+        let first_frame = self.frame_count == 0;
+        if first_frame {
+            self.file.write_all(&PNG_SIGNATURE)?;
+            for chunk in chunks.iter().filter(|chunk| &chunk.kind == b"IHDR") {
+                write_chunk(&mut self.file, &chunk.kind, &chunk.data)?;
+            }
+            self.file.flush()?;
+            self.animation_control_offset = self.file.get_mut().stream_position()?;
+            self.write_animation_control()?;
+        }
+
+        // frame control: size, offset, delay (in milliseconds), no disposal and no blending
+        let mut control: Vec<u8> = Vec::with_capacity(26);
+        control.extend_from_slice(&self.sequence_number.to_be_bytes());
+        control.extend_from_slice(&frame.width().to_be_bytes());
+        control.extend_from_slice(&frame.height().to_be_bytes());
+        control.extend_from_slice(&0u32.to_be_bytes());
+        control.extend_from_slice(&0u32.to_be_bytes());
+        control.extend_from_slice(&(delay_ms.min(u16::MAX as u32) as u16).to_be_bytes());
+        control.extend_from_slice(&1000u16.to_be_bytes());
+        control.extend_from_slice(&[0u8, 0u8]);
+        write_chunk(&mut self.file, b"fcTL", &control)?;
+        self.sequence_number += 1;
+
+        for chunk in chunks.iter().filter(|chunk| &chunk.kind == b"IDAT") {
+            if first_frame {
+                write_chunk(&mut self.file, b"IDAT", &chunk.data)?;
+            } else {
+                let mut data: Vec<u8> = Vec::with_capacity(chunk.data.len() + 4);
+                data.extend_from_slice(&self.sequence_number.to_be_bytes());
+                data.extend_from_slice(&chunk.data);
+                write_chunk(&mut self.file, b"fdAT", &data)?;
+                self.sequence_number += 1;
+            }
+        }
+        self.frame_count += 1;
+        return Ok(());
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let temporary_path = output::temporary_path(&self.path);
+        if self.frame_count == 0 {
+            return fs::remove_file(temporary_path);
+        }
+        write_chunk(&mut self.file, b"IEND", &[])?;
+
+        // go back and fill in the frame count
+        self.file.flush()?;
+        let end = self.file.get_mut().stream_position()?;
+        self.file
+            .get_mut()
+            .seek(SeekFrom::Start(self.animation_control_offset))?;
+        self.write_animation_control()?;
+        self.file.flush()?;
+        self.file.get_mut().seek(SeekFrom::Start(end))?;
+        return fs::rename(temporary_path, &self.path);
+    }
+}
+
+// raw YUV4MPEG2 stream (BT.709, limited range, 4:2:0 with centered chroma) for video encoders
+//
+// the frame rate follows frame_delay_ms, longer delays (the final hold) repeat the frame, odd
+// frame sizes are padded to even ones by repeating the last column and row
+pub struct Y4mSink {
+    writer: BufWriter<Box<dyn Write>>,
+    // a file stream is written to the temporary path and moved here once it is complete
+    path: Option<PathBuf>,
+    frame_delay_ms: u32,
+    header_written: bool,
+}
+
+impl Y4mSink {
+    pub fn new(writer: Box<dyn Write>, frame_delay_ms: u32) -> Y4mSink {
+        return Y4mSink {
+            writer: BufWriter::new(writer),
+            path: None,
+            frame_delay_ms: frame_delay_ms.max(1),
+            header_written: false,
+        };
+    }
+
+    pub fn to_file(path: PathBuf, frame_delay_ms: u32) -> io::Result<Y4mSink> {
+        let file = File::create(output::temporary_path(&path))?;
+        let mut sink = Y4mSink::new(Box::new(file), frame_delay_ms);
+        sink.path = Some(path);
+        return Ok(sink);
+    }
+}
+
+impl FrameSink for Y4mSink {
+    fn add_frame(&mut self, frame: &RgbImage, delay_ms: u32) -> io::Result<()> {
+        let width = (frame.width() + 1) & !1;
+        let height = (frame.height() + 1) & !1;
+        if !self.header_written {
+            writeln!(
+                self.writer,
+                "YUV4MPEG2 W{} H{} F1000:{} Ip A1:1 C420jpeg XYSCSS=420JPEG XCOLORRANGE=LIMITED",
+                width, height, self.frame_delay_ms
+            )?;
+            self.header_written = true;
+        }
+
+        // full resolution luma and chroma, the chroma planes are averaged over 2x2 blocks
+        let plane_size = (width * height) as usize;
+        let mut y_plane: Vec<u8> = Vec::with_capacity(plane_size);
+        let mut cb_full: Vec<f32> = Vec::with_capacity(plane_size);
+        let mut cr_full: Vec<f32> = Vec::with_capacity(plane_size);
+        for y in 0..height {
+            for x in 0..width {
+                let pixel = frame.get_pixel(x.min(frame.width() - 1), y.min(frame.height() - 1));
+                let (luma, cb, cr) = rgb_to_ycbcr(pixel[0], pixel[1], pixel[2]);
+                y_plane.push(luma);
+                cb_full.push(cb);
+                cr_full.push(cr);
+            }
+        }
+        let subsample = |plane: &[f32]| -> Vec<u8> {
+            let mut subsampled: Vec<u8> = Vec::with_capacity(plane_size / 4);
+            for y in (0..height as usize).step_by(2) {
+                for x in (0..width as usize).step_by(2) {
+                    let top = y * width as usize + x;
+                    let bottom = top + width as usize;
+                    let sum = plane[top] + plane[top + 1] + plane[bottom] + plane[bottom + 1];
+                    subsampled.push((sum / 4f32).round().clamp(16f32, 240f32) as u8);
+                }
+            }
+            return subsampled;
+        };
+        let cb_plane = subsample(&cb_full);
+        let cr_plane = subsample(&cr_full);
+
+        // the frame is repeated to cover its delay at the stream's frame rate
+        let repeats = ((delay_ms as f32 / self.frame_delay_ms as f32).round() as u32).max(1);
+        for _ in 0..repeats {
+            self.writer.write_all(b"FRAME\n")?;
+            self.writer.write_all(&y_plane)?;
+            self.writer.write_all(&cb_plane)?;
+            self.writer.write_all(&cr_plane)?;
+        }
+        return Ok(());
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+        match &self.path {
+            Some(path) => return fs::rename(output::temporary_path(path), path),
+            None => return Ok(()),
+        }
+    }
+}
+
+// BT.709 Y'CbCr from gamma encoded sRGB values, luma in limited range (16 - 235), chroma left
+// unrounded for subsampling (16 - 240)
+fn rgb_to_ycbcr(r: u8, g: u8, b: u8) -> (u8, f32, f32) {
+    let (r, g, b) = (r as f32 / 255f32, g as f32 / 255f32, b as f32 / 255f32);
+    let luma = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let cb = (b - luma) / 1.8556;
+    let cr = (r - luma) / 1.5748;
+    return (
+        (16f32 + 219f32 * luma).round() as u8,
+        128f32 + 224f32 * cb,
+        128f32 + 224f32 * cr,
+    );
+}
+
+// numbered PNG files (frame_000000.png, ...), delays are not stored
+pub struct FrameSequenceSink {
+    directory: PathBuf,
+    frame_count: u32,
+}
+
+impl FrameSequenceSink {
+    pub fn new(directory: PathBuf) -> io::Result<FrameSequenceSink> {
+        fs::create_dir_all(&directory)?;
+        return Ok(FrameSequenceSink {
+            directory,
+            frame_count: 0,
+        });
+    }
+}
+
+impl FrameSink for FrameSequenceSink {
+    fn add_frame(&mut self, frame: &RgbImage, _delay_ms: u32) -> io::Result<()> {
+        let path = self
+            .directory
+            .join(format!("frame_{:06}.png", self.frame_count));
+        output::write_atomically(&path, |temporary_path| {
+            return frame
+                .save_with_format(temporary_path, ImageFormat::Png)
+                .map_err(to_io_error);
+        })?;
+        self.frame_count += 1;
+        return Ok(());
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    // remembers the size and delay of every frame
+    struct RecordingSink(Rc<RefCell<Vec<(u32, u32, u32)>>>);
+
+    impl FrameSink for RecordingSink {
+        fn add_frame(&mut self, frame: &RgbImage, delay_ms: u32) -> io::Result<()> {
+            self.0
+                .borrow_mut()
+                .push((frame.width(), frame.height(), delay_ms));
+            return Ok(());
+        }
+
+        fn finish(&mut self) -> io::Result<()> {
+            return Ok(());
+        }
+    }
+
+    #[test]
+    fn frames_follow_placed_pixels_and_the_last_one_is_held() {
+        let frames = Rc::new(RefCell::new(Vec::new()));
+        let options = AnimationOptions {
+            formats: vec![AnimationFormat::Frames],
+            frame_interval: 10,
+            frame_width: Some(4),
+            frame_delay_ms: 40,
+            final_hold_ms: 1500,
+            y4m_output: String::new(),
+        };
+        let mut recorder =
+            AnimationRecorder::with_sinks(options, vec![Box::new(RecordingSink(frames.clone()))]);
+        let image = RgbImage::new(8, 6);
+        for placed_count in 0..25 {
+            recorder.capture(&image, placed_count).unwrap();
+        }
+        recorder.finish(&image, 25).unwrap();
+
+        // frames at 0, 10, 20 and the finished painting at 25, downscaled to 4x3
+        assert_eq!(
+            *frames.borrow(),
+            vec![(4, 3, 40), (4, 3, 40), (4, 3, 40), (4, 3, 1500)]
+        );
+    }
+
+    #[test]
+    fn y4m_frames_are_padded_and_repeated_for_their_delay() {
+        let stream = SharedBuffer::default();
+        let mut sink = Y4mSink::new(Box::new(stream.clone()), 40);
+        let frame = RgbImage::from_fn(5, 3, |x, _| match x {
+            0 => Rgb([255, 255, 255]),
+            _ => Rgb([0, 0, 0]),
+        });
+        sink.add_frame(&frame, 40).unwrap();
+        sink.add_frame(&frame, 120).unwrap();
+        sink.finish().unwrap();
+
+        let bytes = stream.0.borrow();
+        let header_end = bytes.iter().position(|byte| *byte == b'\n').unwrap() + 1;
+        let header = std::str::from_utf8(&bytes[..header_end]).unwrap();
+        assert!(header.starts_with("YUV4MPEG2 W6 H4 F1000:40 "));
+
+        // 4 frames of 6x4 luma plus two 3x2 chroma planes
+        let frame_length = 6 + 6 * 4 + 2 * 3 * 2;
+        assert_eq!(bytes.len(), header_end + 4 * frame_length);
+        let luma = &bytes[header_end + 6..header_end + 6 + 6];
+        assert_eq!(luma, &[235, 16, 16, 16, 16, 16]);
+        let chroma = &bytes[header_end + 6 + 24..header_end + frame_length];
+        assert!(chroma.iter().all(|value| *value == 128));
+    }
+
+    #[test]
+    fn apng_has_one_control_chunk_per_frame() {
+        let path = crate::tests::temp_path("growth.apng");
+        let mut sink = ApngSink::new(path.clone()).unwrap();
+        for shade in [0u8, 128u8, 255u8] {
+            let frame = RgbImage::from_pixel(5, 3, Rgb([shade, shade, shade]));
+            sink.add_frame(&frame, 40).unwrap();
+        }
+        // the file only appears once the animation is complete
+        assert!(!path.exists());
+        sink.finish().unwrap();
+        drop(sink);
+        assert!(!output::temporary_path(&path).exists());
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let chunks = read_chunks(&bytes).unwrap();
+        let kinds: Vec<&[u8; 4]> = chunks.iter().map(|chunk| &chunk.kind).collect();
+        assert_eq!(kinds[0], b"IHDR");
+        assert_eq!(kinds[1], b"acTL");
+        assert_eq!(&chunks[1].data[..4], &3u32.to_be_bytes());
+        assert_eq!(kinds.iter().filter(|kind| **kind == b"fcTL").count(), 3);
+        assert!(kinds.contains(&b"IDAT") && kinds.contains(&b"fdAT"));
+        assert_eq!(kinds[kinds.len() - 1], b"IEND");
+    }
+}
@@ -0,0 +1,564 @@
+use crate::output::{self, Rgb16Image};
+use crate::rng::PaintRng;
+use crate::{pixel_index, CellState, Coordinate, Painter, NO_OWNER};
+use image::RgbImage;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// identifies a checkpoint file, followed by the layout version, the config text, the start
+// time and the deflated painting state
+const MAGIC: &[u8; 8] = b"RCSCKPT\0";
+const VERSION: u32 = 2;
+
+// when the painting state is written
+#[derive(Clone, Debug)]
+pub struct CheckpointOptions {
+    // while painting, None to only write a checkpoint when stopped
+    pub interval: Option<Duration>,
+    // when the painting is stopped by a signal before it is finished
+    pub on_stop: bool,
+    // relative to the output directory
+    pub file_name: String,
+}
+
+// a checkpoint read back from disk, the painting is rebuilt from the config it was painted with
+// before the rest of the state is restored into it
+pub struct Checkpoint {
+    pub config_text: String,
+    // when the interrupted run started, keeps {timestamp} file names stable
+    pub started: SystemTime,
+    // deflated, only inflated once the painting it belongs to bounds its size
+    state: Vec<u8>,
+}
+
+// write the state of a painting, a crash while writing never replaces the previous checkpoint
+// with a partial one
+pub fn write_checkpoint(
+    path: &Path,
+    working_canvas: &Painter,
+    config_text: &str,
+    started: SystemTime,
+) -> io::Result<()> {
+    let bytes = encode_checkpoint(working_canvas, config_text, started);
+    return output::write_atomically(path, |temporary_path| fs::write(temporary_path, bytes));
+}
+
+pub fn read_checkpoint(path: &Path) -> io::Result<Checkpoint> {
+    return decode_checkpoint(&fs::read(path)?);
+}
+
+// everything that changes while painting, derived state (boundry region positions, region
+// sizes, the color cache and the color index) is rebuilt on restore
+pub fn encode_checkpoint(
+    working_canvas: &Painter,
+    config_text: &str,
+    started: SystemTime,
+) -> Vec<u8> {
+    let mut state: Vec<u8> = Vec::new();
+    put_u32(&mut state, working_canvas.canvas_constraints.x_size);
+    put_u32(&mut state, working_canvas.canvas_constraints.y_size);
+    for value in working_canvas.rng.state() {
+        put_u64(&mut state, value);
+    }
+    put_u64(
+        &mut state,
+        working_canvas.canvas_stats.current_pixels_placed_count,
+    );
+    state.extend_from_slice(
+        &working_canvas
+            .canvas_stats
+            .start_time
+            .elapsed()
+            .as_secs_f64()
+            .to_le_bytes(),
+    );
+
+    // how far every finite palette got
+    put_u64(&mut state, working_canvas.color_source.position() as u64);
+    put_u64(&mut state, working_canvas.seed_color_sources.len() as u64);
+    for source in working_canvas.seed_color_sources.iter() {
+        put_u64(&mut state, source.position() as u64);
+    }
+    put_u64(&mut state, working_canvas.region_exhausted.len() as u64);
+    for exhausted in working_canvas.region_exhausted.iter() {
+        state.push(*exhausted as u8);
+    }
+
+    // per location buffers
+    state.extend_from_slice(&working_canvas.image);
+    match &working_canvas.deep_image {
+        Some(deep_image) => {
+            state.push(1);
+            for value in deep_image.iter() {
+                state.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        None => state.push(0),
+    }
+    for cell in working_canvas.occupancy.iter() {
+        state.push(match cell {
+            CellState::Empty => 0,
+            CellState::Frontier => 1,
+            CellState::Filled => 2,
+            CellState::Blocked => 3,
+        });
+    }
+    for owner in working_canvas.owners.iter() {
+        put_u32(&mut state, *owner);
+    }
+    for order in working_canvas.placement_order.iter() {
+        put_u32(&mut state, *order);
+    }
+    for time in working_canvas.placement_times.iter() {
+        state.extend_from_slice(&time.to_le_bytes());
+    }
+
+    // the boundry region in list order
+    put_u64(&mut state, working_canvas.boundry_region_list.len() as u64);
+    for location in working_canvas.boundry_region_list.iter() {
+        put_u32(&mut state, location.x);
+        put_u32(&mut state, location.y);
+    }
+
+    let mut bytes: Vec<u8> = MAGIC.to_vec();
+    put_u32(&mut bytes, VERSION);
+    put_u64(&mut bytes, config_text.len() as u64);
+    bytes.extend_from_slice(config_text.as_bytes());
+    put_u64(
+        &mut bytes,
+        started
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs()),
+    );
+    bytes.extend_from_slice(&miniz_oxide::deflate::compress_to_vec(&state, 6));
+    return bytes;
+}
+
+pub fn decode_checkpoint(bytes: &[u8]) -> io::Result<Checkpoint> {
+    if bytes.len() < 12 || &bytes[..8] != MAGIC {
+        return Err(invalid("not a checkpoint file"));
+    }
+    let version = u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]);
+    if version != VERSION {
+        return Err(invalid(&format!(
+            "checkpoint version {} is not supported, expected {}",
+            version, VERSION
+        )));
+    }
+    let mut reader = StateReader {
+        bytes: &bytes[12..],
+        offset: 0,
+    };
+    let config_length = reader.u64()? as usize;
+    let config_text = String::from_utf8(reader.take(config_length)?.to_vec())
+        .map_err(|_| invalid("the stored config is not UTF-8"))?;
+    let started = UNIX_EPOCH + Duration::from_secs(reader.u64()?);
+    return Ok(Checkpoint {
+        config_text,
+        started,
+        state: bytes[12 + reader.offset..].to_vec(),
+    });
+}
+
+impl Checkpoint {
+    // continue a painting freshly built from the checkpoint's config where the checkpoint
+    // left off, the painting is left untouched unless the whole state is valid
+    pub fn restore(&self, working_canvas: &mut Painter) -> io::Result<()> {
+        let x_size = working_canvas.canvas_constraints.x_size;
+        let y_size = working_canvas.canvas_constraints.y_size;
+        let pixel_count = x_size as usize * y_size as usize;
+        let seed_count = working_canvas.seed_color_sources.len();
+        let region_count = working_canvas.region_exhausted.len();
+        let deep_color = working_canvas.deep_image.is_some();
+
+        // the fixed fields, one value per seed entry and per seed, then the per location buffers
+        // with every location in the boundry region
+        let deep_bytes = if deep_color { 6 } else { 0 };
+        let max_length = 8
+            + 32
+            + 16
+            + 16
+            + seed_count * 8
+            + 8
+            + region_count
+            + 1
+            + 8
+            + pixel_count * (3 + deep_bytes + 13 + 8);
+        let state = miniz_oxide::inflate::decompress_to_vec_with_limit(&self.state, max_length)
+            .map_err(|error| invalid(&format!("corrupt checkpoint data ({:?})", error.status)))?;
+        let mut reader = StateReader {
+            bytes: &state,
+            offset: 0,
+        };
+
+        if (reader.u32()?, reader.u32()?) != (x_size, y_size) {
+            return Err(invalid("the canvas size does not match the stored config"));
+        }
+
+        let mut rng_state = [0u64; 4];
+        for value in rng_state.iter_mut() {
+            *value = reader.u64()?;
+        }
+        // xoshiro never leaves the all zero state, it would only ever return zero
+        if rng_state == [0u64; 4] {
+            return Err(invalid("the random number generator state is all zero"));
+        }
+        let pixels_placed = reader.u64()?;
+        let elapsed = Duration::try_from_secs_f64(reader.f64()?)
+            .map_err(|_| invalid("the painting time is not a duration"))?;
+
+        let source_position = reader.u64()? as usize;
+        if reader.u64()? as usize != seed_count {
+            return Err(invalid("the seed entries do not match the stored config"));
+        }
+        let mut seed_positions: Vec<usize> = Vec::with_capacity(seed_count);
+        for _ in 0..seed_count {
+            seed_positions.push(reader.u64()? as usize);
+        }
+        if reader.u64()? as usize != region_count {
+            return Err(invalid("the seeds do not match the stored config"));
+        }
+        let mut region_exhausted: Vec<bool> = Vec::with_capacity(region_count);
+        for _ in 0..region_count {
+            region_exhausted.push(reader.u8()? != 0);
+        }
+
+        let image = RgbImage::from_raw(x_size, y_size, reader.take(pixel_count * 3)?.to_vec())
+            .ok_or_else(|| invalid("truncated image"))?;
+        if (reader.u8()? != 0) != deep_color {
+            return Err(invalid("the color depth does not match the stored config"));
+        }
+        let deep_image = match deep_color {
+            true => {
+                let mut samples: Vec<u16> = Vec::with_capacity(pixel_count * 3);
+                for _ in 0..pixel_count * 3 {
+                    samples.push(reader.u16()?);
+                }
+                Rgb16Image::from_raw(x_size, y_size, samples)
+            }
+            false => None,
+        };
+        let mut occupancy: Vec<CellState> = Vec::with_capacity(pixel_count);
+        for _ in 0..pixel_count {
+            occupancy.push(match reader.u8()? {
+                0 => CellState::Empty,
+                1 => CellState::Frontier,
+                2 => CellState::Filled,
+                3 => CellState::Blocked,
+                _ => return Err(invalid("unknown location state")),
+            });
+        }
+        let mut owners: Vec<u32> = Vec::with_capacity(pixel_count);
+        for _ in 0..pixel_count {
+            let owner = reader.u32()?;
+            if owner != NO_OWNER && owner as usize >= region_count {
+                return Err(invalid("location owned by an unknown seed"));
+            }
+            owners.push(owner);
+        }
+        let mut placement_order: Vec<u32> = Vec::with_capacity(pixel_count);
+        for _ in 0..pixel_count {
+            placement_order.push(reader.u32()?);
+        }
+        let mut placement_times: Vec<f32> = Vec::with_capacity(pixel_count);
+        for _ in 0..pixel_count {
+            placement_times.push(reader.f32()?);
+        }
+
+        // every boundry region location must be listed exactly once
+        let frontier_length = reader.u64()? as usize;
+        let frontier_cells = occupancy
+            .iter()
+            .filter(|cell| **cell == CellState::Frontier)
+            .count();
+        if frontier_length != frontier_cells {
+            return Err(invalid(
+                "the boundry region does not match the location states",
+            ));
+        }
+        let mut listed: Vec<bool> = vec![false; pixel_count];
+        let mut boundry_region_list: Vec<Coordinate> = Vec::with_capacity(frontier_length);
+        for _ in 0..frontier_length {
+            let location = Coordinate {
+                x: reader.u32()?,
+                y: reader.u32()?,
+            };
+            if location.x >= x_size || location.y >= y_size {
+                return Err(invalid("boundry region location outside the canvas"));
+            }
+            let cell = pixel_index(location.x, location.y, &working_canvas.canvas_constraints);
+            if occupancy[cell] != CellState::Frontier || listed[cell] {
+                return Err(invalid(
+                    "the boundry region does not match the location states",
+                ));
+            }
+            listed[cell] = true;
+            boundry_region_list.push(location);
+        }
+
+        // everything is valid, replace the fresh painting's state
+        working_canvas.rng = PaintRng::from_state(rng_state);
+        working_canvas.canvas_stats.current_pixels_placed_count = pixels_placed;
+        working_canvas.canvas_stats.start_time = Instant::now()
+            .checked_sub(elapsed)
+            .unwrap_or_else(Instant::now);
+        working_canvas.color_source.set_position(source_position);
+        for (source, position) in working_canvas
+            .seed_color_sources
+            .iter_mut()
+            .zip(seed_positions)
+        {
+            source.set_position(position);
+        }
+        working_canvas.region_exhausted = region_exhausted;
+        working_canvas.image = image;
+        working_canvas.deep_image = deep_image;
+        working_canvas.occupancy = occupancy;
+        working_canvas.owners = owners;
+        working_canvas.placement_order = placement_order;
+        working_canvas.placement_times = placement_times;
+        for (list_index, location) in boundry_region_list.iter().enumerate() {
+            let cell = pixel_index(location.x, location.y, &working_canvas.canvas_constraints);
+            working_canvas.boundry_region_positions[cell] = list_index;
+        }
+        working_canvas.boundry_region_list = boundry_region_list;
+
+        rebuild_derived_state(working_canvas);
+        return Ok(());
+    }
+}
+
+// recompute what place_pixel keeps up to date from the restored buffers
+fn rebuild_derived_state(working_canvas: &mut Painter) {
+    let x_size = working_canvas.canvas_constraints.x_size;
+    let y_size = working_canvas.canvas_constraints.y_size;
+
+    for (cell, pixel) in working_canvas.image.pixels().enumerate() {
+        if working_canvas.occupancy[cell] == CellState::Filled {
+            working_canvas.color_cache[cell] = working_canvas.distance_metric.convert(*pixel);
+        }
+    }
+
+    working_canvas.region_frontier_counts.fill(0);
+    for location in working_canvas.boundry_region_list.iter() {
+        let owner = working_canvas.owners
+            [pixel_index(location.x, location.y, &working_canvas.canvas_constraints)];
+        if owner != NO_OWNER {
+            working_canvas.region_frontier_counts[owner as usize] += 1;
+        }
+    }
+
+    // report the boundry region to the selector again, selectors answer queries by score, tie
+    // key and location so the order of the events does not matter
+    working_canvas.position_selector.clear();
+    for location in working_canvas.boundry_region_list.iter() {
+        let cell = pixel_index(location.x, location.y, &working_canvas.canvas_constraints);
+        working_canvas.position_selector.frontier_added(cell);
+        for (neighbor, _) in working_canvas
+            .scoring_neighborhood
+            .around(*location, x_size, y_size)
+        {
+            let neighbor = pixel_index(neighbor.x, neighbor.y, &working_canvas.canvas_constraints);
+            if working_canvas.occupancy[neighbor] == CellState::Filled {
+                working_canvas
+                    .position_selector
+                    .neighbor_painted(cell, working_canvas.color_cache[neighbor]);
+            }
+        }
+    }
+}
+
+fn put_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(bytes: &mut Vec<u8>, value: u64) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn invalid(message: &str) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, message);
+}
+
+// reads the little endian values written by encode_checkpoint in order
+struct StateReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> StateReader<'a> {
+    fn take(&mut self, length: usize) -> io::Result<&'a [u8]> {
+        let end = self
+            .offset
+            .checked_add(length)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| invalid("truncated checkpoint"))?;
+        let taken = &self.bytes[self.offset..end];
+        self.offset = end;
+        return Ok(taken);
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        return Ok(self.take(1)?[0]);
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        let bytes = self.take(2)?;
+        return Ok(u16::from_le_bytes([bytes[0], bytes[1]]));
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        return Ok(u32::from_le_bytes(bytes));
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        return Ok(u64::from_le_bytes(bytes));
+    }
+
+    fn f32(&mut self) -> io::Result<f32> {
+        return Ok(f32::from_bits(self.u32()?));
+    }
+
+    fn f64(&mut self) -> io::Result<f64> {
+        return Ok(f64::from_bits(self.u64()?));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{self, Config};
+    use crate::{metadata, PaintingBuilder};
+
+    fn painter() -> Painter {
+        return PaintingBuilder::new(8, 8)
+            .starting_location(1, 1)
+            .starting_location(6, 6)
+            .rng_seed(9)
+            .build()
+            .unwrap();
+    }
+
+    // encode a painting part way through after corrupting it, then restore it into a fresh one
+    fn restore_corrupted(corrupt: impl Fn(&mut Painter)) -> io::Result<()> {
+        let mut interrupted = painter();
+        interrupted.run_until(20);
+        corrupt(&mut interrupted);
+        let checkpoint =
+            decode_checkpoint(&encode_checkpoint(&interrupted, "", SystemTime::now()))?;
+        return checkpoint.restore(&mut painter());
+    }
+
+    const CONFIG_TEXT: &str = "config:
+  seed: \"5\"
+  canvas:
+    size:
+      x: \"16\"
+      y: \"12\"
+    starting_locations:
+      - x: 2
+        y: 3
+      - x: 12
+        y: 9
+  colors:
+    generation_mode: \"palette\"
+    palette:
+      bit_depth: \"3\"
+    generation_color_space: \"rgb\"
+";
+
+    #[test]
+    fn stopped_run_resumes_from_its_checkpoint_file() {
+        let config_yaml = config::parse_config(CONFIG_TEXT, "test").unwrap();
+        let config = Config::from_yaml(&config_yaml).unwrap();
+        let mut uninterrupted = PaintingBuilder::from_config(&config).build().unwrap();
+        uninterrupted.run_to_completion();
+
+        // stop part way and write the checkpoint the way a stopped run does
+        let mut stopped = PaintingBuilder::from_config(&config).build().unwrap();
+        assert!(stopped.run_until(70));
+        let path = crate::tests::temp_path("stopped.ckpt");
+        let config_text = metadata::resolved_config_text(&config_yaml, stopped.seed());
+        write_checkpoint(&path, &stopped, &config_text, SystemTime::now()).unwrap();
+
+        // resume from the file alone, with the config stored in it
+        let checkpoint = read_checkpoint(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let stored_config =
+            Config::from_yaml(&config::parse_config(&checkpoint.config_text, "test").unwrap())
+                .unwrap();
+        let mut resumed = PaintingBuilder::from_config(&stored_config)
+            .build()
+            .unwrap();
+        checkpoint.restore(&mut resumed).unwrap();
+        assert_eq!(resumed.stats().current_pixels_placed_count, 70);
+        resumed.run_to_completion();
+
+        assert!(resumed.image() == uninterrupted.image());
+        assert_eq!(resumed.placement_order, uninterrupted.placement_order);
+        assert_eq!(resumed.owners, uninterrupted.owners);
+    }
+
+    #[test]
+    fn rejects_corrupt_checkpoints() {
+        assert!(restore_corrupted(|_| {}).is_ok());
+
+        let owner_of_unknown_seed = restore_corrupted(|working_canvas| {
+            let location = working_canvas.boundry_region_list[0];
+            let cell = pixel_index(location.x, location.y, &working_canvas.canvas_constraints);
+            working_canvas.owners[cell] = 2;
+        });
+        let painted_frontier = restore_corrupted(|working_canvas| {
+            working_canvas.boundry_region_list[0] = Coordinate { x: 1, y: 1 };
+        });
+        let duplicate_frontier = restore_corrupted(|working_canvas| {
+            working_canvas.boundry_region_list[1] = working_canvas.boundry_region_list[0];
+        });
+        for result in [owner_of_unknown_seed, painted_frontier, duplicate_frontier] {
+            assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        }
+
+        // a truncated owner buffer
+        let mut interrupted = painter();
+        interrupted.run_until(20);
+        let mut checkpoint =
+            decode_checkpoint(&encode_checkpoint(&interrupted, "", SystemTime::now())).unwrap();
+        let mut state = miniz_oxide::inflate::decompress_to_vec(&checkpoint.state).unwrap();
+        // owners are followed by the placement orders and times and the boundry region
+        let frontier_bytes = 8 + interrupted.boundry_region_list.len() * 8;
+        let owners_end = state.len() - frontier_bytes - 2 * 64 * 4;
+        state.truncate(owners_end - 2);
+        checkpoint.state = miniz_oxide::deflate::compress_to_vec(&state, 6);
+        let mut fresh = painter();
+        assert_eq!(
+            checkpoint.restore(&mut fresh).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+
+        // nothing read before the error was kept
+        let untouched = painter();
+        assert_eq!(fresh.rng.state(), untouched.rng.state());
+        assert_eq!(fresh.owners, untouched.owners);
+        assert!(fresh.occupancy == untouched.occupancy);
+        assert!(fresh.image() == untouched.image());
+
+        // a generator that could only ever return zero
+        let zero_rng = restore_corrupted(|working_canvas| {
+            working_canvas.rng = PaintRng::from_state([0u64; 4]);
+        });
+        assert_eq!(zero_rng.unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        // state that inflates past the largest painting of this size
+        checkpoint.state = miniz_oxide::deflate::compress_to_vec(&vec![0u8; 1 << 20], 6);
+        assert_eq!(
+            checkpoint.restore(&mut painter()).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+}
@@ -0,0 +1,191 @@
+pub const USAGE: &str = "usage: rusty-color-shredder [validate] [options]
+
+commands:
+  validate                  check the config and exit without rendering
+  extract-config <png>      print the config stored in a PNG written by this program, which
+                            reproduces it (e.g. > config/reproduce.yaml)
+  resume <checkpoint>       continue an interrupted render from a checkpoint, painted with the
+                            config stored in it
+
+options:
+  --config <path>           config file to read (default: ./config/config.yaml)
+  --output-dir <path>       directory the output images are written to (default: ./output)
+  --width <pixels>          canvas width, same as --set canvas.size.x=<pixels>
+  --height <pixels>         canvas height, same as --set canvas.size.y=<pixels>
+  --seed <seed>             random seed, same as --set seed=<seed>
+  --set <key.path>=<value>  override any config value, keys are relative to the \"config\"
+                            block and list entries are addressed by index
+                            (e.g. --set canvas.starting_locations.0.x=10)
+  --help                    print this message";
+
+// what the program should do with the config
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Command {
+    Render,
+    Validate,
+    ExtractConfig,
+    Resume,
+}
+
+// everything given on the command line
+pub struct Arguments {
+    pub command: Command,
+    pub config_path: String,
+    pub output_dir: String,
+    // the painting to read the config from for extract-config, the checkpoint for resume
+    pub input_path: String,
+    pub overrides: Vec<(String, String)>,
+    pub help: bool,
+}
+
+// parse the command line arguments (without the program name)
+pub fn parse_arguments(mut args: impl Iterator<Item = String>) -> Result<Arguments, String> {
+    let mut arguments = Arguments {
+        command: Command::Render,
+        config_path: String::from("./config/config.yaml"),
+        output_dir: String::from("./output"),
+        input_path: String::new(),
+        overrides: Vec::new(),
+        help: false,
+    };
+
+    while let Some(flag) = args.next() {
+        // every option except --help takes a value
+        if flag == "--help" || flag == "-h" {
+            arguments.help = true;
+            continue;
+        }
+        if flag == "validate" {
+            arguments.command = Command::Validate;
+            continue;
+        }
+        if flag == "extract-config" {
+            arguments.command = Command::ExtractConfig;
+            arguments.input_path = args
+                .next()
+                .ok_or_else(|| String::from("missing painting for extract-config"))?;
+            continue;
+        }
+        if flag == "resume" {
+            arguments.command = Command::Resume;
+            arguments.input_path = args
+                .next()
+                .ok_or_else(|| String::from("missing checkpoint for resume"))?;
+            continue;
+        }
+        // the value is only read once the option is known, so an unknown option never takes
+        // the argument after it
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("missing value for {}", flag))
+        };
+
+        match flag.as_str() {
+            "--config" => arguments.config_path = value()?,
+            "--output-dir" => arguments.output_dir = value()?,
+            "--width" => arguments
+                .overrides
+                .push((String::from("canvas.size.x"), value()?)),
+            "--height" => arguments
+                .overrides
+                .push((String::from("canvas.size.y"), value()?)),
+            "--seed" => arguments.overrides.push((String::from("seed"), value()?)),
+            "--set" => {
+                let value = value()?;
+                let (key_path, key_value) = value
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected key.path=value for --set, got {}", value))?;
+                arguments
+                    .overrides
+                    .push((String::from(key_path), String::from(key_value)));
+            }
+            _ => return Err(format!("unknown option {}", flag)),
+        }
+    }
+
+    // a resumed render has to use the config it started with
+    if arguments.command == Command::Resume && !arguments.overrides.is_empty() {
+        return Err(String::from(
+            "resume uses the config stored in the checkpoint, it can not be overridden",
+        ));
+    }
+
+    return Ok(arguments);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusty_color_shredder::config::{self, CanvasSize, Config};
+
+    fn parse(args: &[&str]) -> Result<Arguments, String> {
+        return parse_arguments(args.iter().map(|arg| String::from(*arg)));
+    }
+
+    #[test]
+    fn size_and_seed_flags_override_the_file() {
+        let arguments = parse(&[
+            "validate",
+            "--width",
+            "40",
+            "--set",
+            "canvas.size.y=30",
+            "--seed",
+            "7",
+        ])
+        .unwrap();
+        assert!(arguments.command == Command::Validate);
+
+        let mut yaml = config::parse_config(
+            "config:
+  seed: \"1\"
+  canvas:
+    size:
+      x: \"8\"
+      y: \"8\"
+    starting_locations:
+      - x: 0
+        y: 0
+  colors:
+    generation_mode: \"random\"
+    generation_color_space: \"rgb\"
+",
+            "test",
+        )
+        .unwrap();
+        for (key_path, value) in arguments.overrides.iter() {
+            config::apply_override(&mut yaml, key_path, value).unwrap();
+        }
+        let config = Config::from_yaml(&yaml).unwrap();
+        assert_eq!(
+            config.canvas.size,
+            CanvasSize::Fixed {
+                x_size: 40,
+                y_size: 30
+            }
+        );
+        assert_eq!(config.seed, Some(7));
+    }
+
+    #[test]
+    fn rejects_malformed_arguments() {
+        assert!(parse(&["--set", "canvas.size.x"]).is_err());
+        assert!(parse(&["--width"]).is_err());
+        assert!(parse(&["--colour", "red"]).is_err());
+        // an unknown option is reported as such, not as the value it would have taken
+        assert_eq!(
+            parse(&["--bogus", "--help"]).err(),
+            Some(String::from("unknown option --bogus"))
+        );
+        assert_eq!(
+            parse(&["--bogus"]).err(),
+            Some(String::from("unknown option --bogus"))
+        );
+        assert!(parse(&["resume"]).is_err());
+
+        // a resumed render keeps the config stored in its checkpoint
+        assert!(parse(&["resume", "painting.ckpt"]).is_ok());
+        assert!(parse(&["resume", "painting.ckpt", "--seed", "3"]).is_err());
+        assert!(parse(&["--set", "seed=3", "resume", "painting.ckpt"]).is_err());
+    }
+}
@@ -0,0 +1,651 @@
+use crate::rng::PaintRng;
+use image::Rgb;
+use palette::{FromColor, Hsl, Hsv, Hwb, Lab, Srgb};
+use rand::{seq::SliceRandom, Rng};
+
+// color spaces a color can be generated in before being converted to RGB
+#[derive(Copy, Clone)]
+pub enum ColorSpace {
+    Rgb,
+    Hsv,
+    Hsl,
+    Hwb,
+}
+
+// generation options for a single channel, values are normalized to 0.0 - 1.0
+// (hue channels are scaled to 0 - 360 degrees when the color is built)
+#[derive(Copy, Clone)]
+pub struct ChannelOptions {
+    pub lock: Option<f32>,
+    pub min: f32,
+    pub max: f32,
+}
+
+// everything needed to generate a random color
+#[derive(Copy, Clone)]
+pub struct ColorOptions {
+    pub color_space: ColorSpace,
+    pub channels: [ChannelOptions; 3],
+}
+
+// color spaces a reference color can be perturbed in
+#[derive(Copy, Clone)]
+pub enum SpreadSpace {
+    Rgb,
+    Lab,
+}
+
+// a reference color and its relative weight when choosing between references
+#[derive(Copy, Clone)]
+pub struct ReferenceColor {
+    pub color: Rgb<u8>,
+    pub weight: f32,
+}
+
+// everything needed to generate colors around a list of reference colors
+#[derive(Clone)]
+pub struct ReferenceOptions {
+    pub references: Vec<ReferenceColor>,
+    pub spread_space: SpreadSpace,
+    pub spread: [f32; 3],
+}
+
+// everything needed to build an exhaustive palette
+#[derive(Copy, Clone)]
+pub struct PaletteOptions {
+    pub color_options: ColorOptions,
+    pub bit_depth: u32,
+    pub group_by_channel: usize,
+    pub shuffle: bool,
+}
+
+// the order a sorted palette hands out its colors in, by the HSL value of each color
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PaletteOrder {
+    Hue,
+    Saturation,
+    Lightness,
+}
+
+// the colors of an image to sample from
+#[derive(Clone)]
+pub struct ImageOptions {
+    pub colors: Vec<Rgb<u8>>,
+    // every pixel's color is placed exactly once instead of drawing pixels at random
+    pub exhaustive: bool,
+}
+
+// the color generation strategy chosen in config
+#[derive(Clone)]
+pub enum ColorConfig {
+    Random(ColorOptions),
+    Reference(ReferenceOptions),
+    Palette(PaletteOptions),
+    SortedPalette(PaletteOptions, PaletteOrder),
+    Image(ImageOptions),
+}
+
+// a stream of colors to place, new strategies only need to implement this to be painted with
+// (see PaintingBuilder::custom_color_source)
+pub trait ColorSource: Send + Sync {
+    // the next color to place, None once a finite source is exhausted, colors keep 16 bits per
+    // channel (palettes and images only hold 8-bit colors) and are truncated for painting
+    fn next_color(&mut self, rng: &mut PaintRng) -> Option<Rgb<u16>>;
+
+    // number of colors left to place, None for endless sources
+    fn remaining(&self) -> Option<usize> {
+        return None;
+    }
+
+    // how many colors a finite source has handed out, always 0 for endless sources
+    fn position(&self) -> usize {
+        return 0;
+    }
+
+    // continue a finite source from a position returned by position()
+    fn set_position(&mut self, _position: usize) {}
+
+    // start over from the first color
+    fn reset(&mut self) {
+        self.set_position(0);
+    }
+}
+
+// a finite, precomputed list of colors consumed in order
+pub struct Palette {
+    pub colors: Vec<Rgb<u8>>,
+    pub index: usize,
+}
+
+// colors drawn from the pixels of an image, in random order
+pub struct ImageSamples {
+    colors: Vec<Rgb<u8>>,
+    index: usize,
+    exhaustive: bool,
+}
+
+impl ColorSpace {
+    pub fn from_name(name: &str) -> Option<ColorSpace> {
+        match name.to_lowercase().as_str() {
+            "rgb" => Some(ColorSpace::Rgb),
+            "hsv" => Some(ColorSpace::Hsv),
+            "hsl" => Some(ColorSpace::Hsl),
+            "hwb" => Some(ColorSpace::Hwb),
+            _ => None,
+        }
+    }
+
+    // build an sRGB color from three normalized channel values
+    pub fn to_srgb(self, channels: [f32; 3]) -> Srgb {
+        match self {
+            ColorSpace::Rgb => Srgb::new(channels[0], channels[1], channels[2]),
+            ColorSpace::Hsv => {
+                Srgb::from_color(Hsv::new(channels[0] * 360f32, channels[1], channels[2]))
+            }
+            ColorSpace::Hsl => {
+                Srgb::from_color(Hsl::new(channels[0] * 360f32, channels[1], channels[2]))
+            }
+            ColorSpace::Hwb => {
+                Srgb::from_color(Hwb::new(channels[0] * 360f32, channels[1], channels[2]))
+            }
+        }
+    }
+}
+
+impl SpreadSpace {
+    pub fn from_name(name: &str) -> Option<SpreadSpace> {
+        match name.to_lowercase().as_str() {
+            "rgb" => Some(SpreadSpace::Rgb),
+            "lab" => Some(SpreadSpace::Lab),
+            _ => None,
+        }
+    }
+}
+
+impl ChannelOptions {
+    // draw a value uniformly inside the clamp range, or return the locked value
+    fn generate_value(&self, rng: &mut PaintRng) -> f32 {
+        match self.lock {
+            Some(value) => value,
+            None => self.min + (self.max - self.min) * rng.gen::<f32>(),
+        }
+    }
+}
+
+impl ColorConfig {
+    // whether the colors run out, only finite colors can size a canvas
+    pub fn is_finite(&self) -> bool {
+        match self {
+            ColorConfig::Random(_) | ColorConfig::Reference(_) => false,
+            ColorConfig::Palette(_) | ColorConfig::SortedPalette(..) => true,
+            ColorConfig::Image(options) => options.exhaustive,
+        }
+    }
+}
+
+impl PaletteOrder {
+    pub fn from_name(name: &str) -> Option<PaletteOrder> {
+        match name.to_lowercase().as_str() {
+            "hue" => Some(PaletteOrder::Hue),
+            "saturation" => Some(PaletteOrder::Saturation),
+            "lightness" => Some(PaletteOrder::Lightness),
+            _ => None,
+        }
+    }
+
+    fn key(self, color: Rgb<u8>) -> f32 {
+        let hsl = Hsl::from_color(rgb8_to_srgb(color));
+        match self {
+            PaletteOrder::Hue => hsl.hue.to_positive_degrees(),
+            PaletteOrder::Saturation => hsl.saturation,
+            PaletteOrder::Lightness => hsl.lightness,
+        }
+    }
+}
+
+impl ColorOptions {
+    // generate a random color within the configured channel ranges
+    pub fn generate_color(&self, rng: &mut PaintRng) -> Rgb<u16> {
+        let color = self.color_space.to_srgb([
+            self.channels[0].generate_value(rng),
+            self.channels[1].generate_value(rng),
+            self.channels[2].generate_value(rng),
+        ]);
+        return srgb_to_rgb16(color);
+    }
+}
+
+impl ReferenceOptions {
+    // choose a reference by weight and perturb each of its channels uniformly inside +/- spread
+    pub fn generate_color(&self, rng: &mut PaintRng) -> Rgb<u16> {
+        let total_weight: f32 = self.references.iter().map(|r| r.weight).sum();
+        let mut choice = rng.gen::<f32>() * total_weight;
+        let mut reference = self.references[self.references.len() - 1].color;
+        for candidate in &self.references {
+            if choice < candidate.weight {
+                reference = candidate.color;
+                break;
+            }
+            choice -= candidate.weight;
+        }
+
+        let offsets = [
+            (rng.gen::<f32>() * 2f32 - 1f32) * self.spread[0],
+            (rng.gen::<f32>() * 2f32 - 1f32) * self.spread[1],
+            (rng.gen::<f32>() * 2f32 - 1f32) * self.spread[2],
+        ];
+
+        match self.spread_space {
+            // spread is given in 8-bit channel units
+            SpreadSpace::Rgb => {
+                return Rgb([
+                    ((reference[0] as f32 + offsets[0]).clamp(0f32, 255f32) * 257f32) as u16,
+                    ((reference[1] as f32 + offsets[1]).clamp(0f32, 255f32) * 257f32) as u16,
+                    ((reference[2] as f32 + offsets[2]).clamp(0f32, 255f32) * 257f32) as u16,
+                ]);
+            }
+            // spread is given in CIELAB units (L: 0 - 100, a/b: roughly -128 - 127)
+            SpreadSpace::Lab => {
+                let lab = Lab::from_color(rgb8_to_srgb(reference));
+                let perturbed =
+                    Lab::new(lab.l + offsets[0], lab.a + offsets[1], lab.b + offsets[2]);
+                return srgb_to_rgb16(Srgb::from_color(perturbed));
+            }
+        }
+    }
+}
+
+impl Palette {
+    // build every color of the cube described by the palette options
+    pub fn new(options: &PaletteOptions, rng: &mut PaintRng) -> Palette {
+        return Palette {
+            colors: generate_palette(
+                &options.color_options,
+                options.bit_depth,
+                options.group_by_channel,
+                options.shuffle,
+                rng,
+            ),
+            index: 0,
+        };
+    }
+
+    // the palette's colors ordered by their HSL value, equal colors keep the generated order
+    pub fn sorted(options: &PaletteOptions, order: PaletteOrder, rng: &mut PaintRng) -> Palette {
+        let mut palette = Palette::new(options, rng);
+        // the HSL conversion is done once per color rather than once per comparison
+        let mut keyed: Vec<(f32, Rgb<u8>)> = palette
+            .colors
+            .iter()
+            .map(|color| (order.key(*color), *color))
+            .collect();
+        keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+        palette.colors = keyed.into_iter().map(|(_, color)| color).collect();
+        return palette;
+    }
+}
+
+impl ImageSamples {
+    // an exhaustive image hands out its pixels shuffled once, so every color is placed once
+    pub fn new(options: &ImageOptions, rng: &mut PaintRng) -> ImageSamples {
+        let mut colors = options.colors.clone();
+        if options.exhaustive {
+            colors.shuffle(rng);
+        }
+        return ImageSamples {
+            colors,
+            index: 0,
+            exhaustive: options.exhaustive,
+        };
+    }
+}
+
+impl ColorSource for ColorOptions {
+    fn next_color(&mut self, rng: &mut PaintRng) -> Option<Rgb<u16>> {
+        return Some(self.generate_color(rng));
+    }
+}
+
+impl ColorSource for ReferenceOptions {
+    fn next_color(&mut self, rng: &mut PaintRng) -> Option<Rgb<u16>> {
+        return Some(self.generate_color(rng));
+    }
+}
+
+impl ColorSource for Palette {
+    // take the next unused color from the palette
+    fn next_color(&mut self, _rng: &mut PaintRng) -> Option<Rgb<u16>> {
+        let color = self.colors.get(self.index).copied();
+        self.index += 1;
+        return color.map(rgb8_to_rgb16);
+    }
+
+    fn remaining(&self) -> Option<usize> {
+        return Some(self.colors.len().saturating_sub(self.index));
+    }
+
+    fn position(&self) -> usize {
+        return self.index;
+    }
+
+    fn set_position(&mut self, position: usize) {
+        self.index = position;
+    }
+}
+
+impl ColorSource for ImageSamples {
+    fn next_color(&mut self, rng: &mut PaintRng) -> Option<Rgb<u16>> {
+        if !self.exhaustive {
+            return Some(rgb8_to_rgb16(
+                self.colors[rng.gen_range(0..self.colors.len())],
+            ));
+        }
+        let color = self.colors.get(self.index).copied();
+        self.index += 1;
+        return color.map(rgb8_to_rgb16);
+    }
+
+    fn remaining(&self) -> Option<usize> {
+        return match self.exhaustive {
+            true => Some(self.colors.len().saturating_sub(self.index)),
+            false => None,
+        };
+    }
+
+    fn position(&self) -> usize {
+        return self.index;
+    }
+
+    fn set_position(&mut self, position: usize) {
+        self.index = position;
+    }
+}
+
+// build the color source selected by the color config
+pub fn color_source(config: &ColorConfig, rng: &mut PaintRng) -> Box<dyn ColorSource> {
+    match config {
+        ColorConfig::Random(options) => Box::new(*options),
+        ColorConfig::Reference(options) => Box::new(options.clone()),
+        ColorConfig::Palette(options) => Box::new(Palette::new(options, rng)),
+        ColorConfig::SortedPalette(options, order) => {
+            Box::new(Palette::sorted(options, *order, rng))
+        }
+        ColorConfig::Image(options) => Box::new(ImageSamples::new(options, rng)),
+    }
+}
+
+// build every color of a cube with 2^bit_depth values per channel, spread over each channel's
+// clamp range (locked channels contribute a single value), truncate to 8-bit RGB and drop
+// duplicates created by the truncation
+fn generate_palette(
+    options: &ColorOptions,
+    bit_depth: u32,
+    group_by_channel: usize,
+    shuffle: bool,
+    rng: &mut PaintRng,
+) -> Vec<Rgb<u8>> {
+    // values taken by each channel, shuffled so that grouped (unshuffled) palettes still vary
+    let mut channel_values: Vec<Vec<f32>> = Vec::new();
+    for channel in options.channels.iter() {
+        let mut values: Vec<f32> = match channel.lock {
+            Some(value) => vec![value],
+            None => {
+                let steps = 2u32.pow(bit_depth) - 1;
+                (0..=steps)
+                    .map(|step| {
+                        channel.min + (channel.max - channel.min) * step as f32 / steps as f32
+                    })
+                    .collect()
+            }
+        };
+        values.shuffle(rng);
+        channel_values.push(values);
+    }
+
+    // the grouped channel is iterated in the outermost loop
+    let outer = group_by_channel - 1;
+    let middle = (outer + 1) % 3;
+    let inner = (outer + 2) % 3;
+
+    // one bit per 24-bit color, set once a color has been added
+    let mut seen = vec![0u64; (1 << 24) / 64];
+    let mut color_list: Vec<Rgb<u8>> = Vec::new();
+    let mut color_value = [0f32; 3];
+
+    // loop over the entire color space
+    for outer_value in channel_values[outer].iter() {
+        color_value[outer] = *outer_value;
+        for middle_value in channel_values[middle].iter() {
+            color_value[middle] = *middle_value;
+            for inner_value in channel_values[inner].iter() {
+                color_value[inner] = *inner_value;
+
+                // convert to RGB and skip colors already in the list
+                let color = srgb_to_rgb8(options.color_space.to_srgb(color_value));
+                let key = (color[0] as usize) << 16 | (color[1] as usize) << 8 | color[2] as usize;
+                if seen[key / 64] & (1 << (key % 64)) != 0 {
+                    continue;
+                }
+                seen[key / 64] |= 1 << (key % 64);
+                color_list.push(color);
+            }
+        }
+    }
+
+    // final shuffle, removes channel sub-grouping
+    if shuffle {
+        color_list.shuffle(rng);
+    }
+
+    return color_list;
+}
+
+// convert an 8-bit color to floating point sRGB
+pub fn rgb8_to_srgb(color: Rgb<u8>) -> Srgb {
+    return Srgb::new(
+        color[0] as f32 / 255f32,
+        color[1] as f32 / 255f32,
+        color[2] as f32 / 255f32,
+    );
+}
+
+// truncate a floating point sRGB color to 8 bits per channel
+pub fn srgb_to_rgb8(color: Srgb) -> Rgb<u8> {
+    return Rgb([
+        (color.red.clamp(0f32, 1f32) * 255f32).floor() as u8,
+        (color.green.clamp(0f32, 1f32) * 255f32).floor() as u8,
+        (color.blue.clamp(0f32, 1f32) * 255f32).floor() as u8,
+    ]);
+}
+
+// round a floating point sRGB color to 16 bits per channel
+pub fn srgb_to_rgb16(color: Srgb) -> Rgb<u16> {
+    return Rgb([
+        (color.red.clamp(0f32, 1f32) * 65535f32).round() as u16,
+        (color.green.clamp(0f32, 1f32) * 65535f32).round() as u16,
+        (color.blue.clamp(0f32, 1f32) * 65535f32).round() as u16,
+    ]);
+}
+
+// widen an 8-bit color to 16 bits per channel, 255 maps to 65535
+pub fn rgb8_to_rgb16(color: Rgb<u8>) -> Rgb<u16> {
+    return Rgb([
+        color[0] as u16 * 257,
+        color[1] as u16 * 257,
+        color[2] as u16 * 257,
+    ]);
+}
+
+// truncate a 16-bit color to 8 bits per channel, the inverse of rgb8_to_rgb16
+pub fn rgb16_to_rgb8(color: Rgb<u16>) -> Rgb<u8> {
+    return Rgb([
+        (color[0] / 257) as u8,
+        (color[1] / 257) as u8,
+        (color[2] / 257) as u8,
+    ]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // every color of a finite source is handed out once, reset starts over
+    fn drain(source: &mut dyn ColorSource, rng: &mut PaintRng) -> Vec<Rgb<u8>> {
+        let mut colors: Vec<Rgb<u8>> = Vec::new();
+        while let Some(color) = source.next_color(rng) {
+            colors.push(rgb16_to_rgb8(color));
+        }
+        assert_eq!(source.remaining(), Some(0));
+        return colors;
+    }
+
+    #[test]
+    fn locked_and_clamped_channels_stay_in_range() {
+        let mut rng = PaintRng::from_seed(4);
+        let options = ColorOptions {
+            color_space: ColorSpace::Rgb,
+            channels: [
+                ChannelOptions {
+                    lock: Some(0.5f32),
+                    min: 0f32,
+                    max: 1f32,
+                },
+                ChannelOptions {
+                    lock: None,
+                    min: 0.2f32,
+                    max: 0.4f32,
+                },
+                ChannelOptions {
+                    lock: None,
+                    min: 0f32,
+                    max: 1f32,
+                },
+            ],
+        };
+        let low = (0.2f32 * 65535f32).round() as u16;
+        let high = (0.4f32 * 65535f32).round() as u16;
+        for _ in 0..1000 {
+            let color = options.generate_color(&mut rng);
+            assert_eq!(color[0], 32768);
+            assert!((low..=high).contains(&color[1]));
+        }
+    }
+
+    #[test]
+    fn reference_weights_shift_the_choice() {
+        let mut rng = PaintRng::from_seed(6);
+        let red = Rgb([255u8, 0, 0]);
+        let blue = Rgb([0u8, 0, 255]);
+        let red_share = |red_weight: f32, rng: &mut PaintRng| {
+            let options = ReferenceOptions {
+                references: vec![
+                    ReferenceColor {
+                        color: red,
+                        weight: red_weight,
+                    },
+                    ReferenceColor {
+                        color: blue,
+                        weight: 1f32,
+                    },
+                ],
+                spread_space: SpreadSpace::Rgb,
+                spread: [0f32; 3],
+            };
+            let mut reds = 0;
+            for _ in 0..2000 {
+                let color = rgb16_to_rgb8(options.generate_color(rng));
+                assert!(color == red || color == blue);
+                reds += (color == red) as u32;
+            }
+            return reds as f32 / 2000f32;
+        };
+
+        // 1:1 and 9:1 weights, with a margin of several standard deviations
+        assert!((red_share(1f32, &mut rng) - 0.5f32).abs() < 0.05f32);
+        assert!((red_share(9f32, &mut rng) - 0.9f32).abs() < 0.05f32);
+    }
+
+    #[test]
+    fn palettes_drop_colors_repeated_by_truncation() {
+        let mut rng = PaintRng::from_seed(2);
+        let locked = ChannelOptions {
+            lock: Some(0f32),
+            min: 0f32,
+            max: 1f32,
+        };
+        // 16 red values between 0 and 5.1 truncate to the 6 values 0 to 5
+        let narrow = ChannelOptions {
+            lock: None,
+            min: 0f32,
+            max: 0.02f32,
+        };
+        let options = ColorOptions {
+            color_space: ColorSpace::Rgb,
+            channels: [narrow, locked, locked],
+        };
+        let mut colors = generate_palette(&options, 4, 1, true, &mut rng);
+        colors.sort_by_key(|color| color[0]);
+        assert_eq!(
+            colors,
+            (0..6u8).map(|red| Rgb([red, 0, 0])).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn finite_sources_place_every_color_once() {
+        let mut rng = PaintRng::from_seed(9);
+        let channel = ChannelOptions {
+            lock: None,
+            min: 0f32,
+            max: 1f32,
+        };
+        let options = PaletteOptions {
+            color_options: ColorOptions {
+                color_space: ColorSpace::Rgb,
+                channels: [channel; 3],
+            },
+            bit_depth: 2,
+            group_by_channel: 1,
+            shuffle: true,
+        };
+
+        let mut sorted = color_source(
+            &ColorConfig::SortedPalette(options, PaletteOrder::Lightness),
+            &mut rng,
+        );
+        assert_eq!(sorted.remaining(), Some(64));
+        let colors = drain(sorted.as_mut(), &mut rng);
+        assert_eq!(colors.len(), 64);
+        assert!(colors.windows(2).all(|pair| {
+            PaletteOrder::Lightness.key(pair[0]) <= PaletteOrder::Lightness.key(pair[1])
+        }));
+        sorted.reset();
+        assert_eq!(drain(sorted.as_mut(), &mut rng), colors);
+
+        let pixels: Vec<Rgb<u8>> = (0..10u8).map(|value| Rgb([value, 0, 255])).collect();
+        let mut image = color_source(
+            &ColorConfig::Image(ImageOptions {
+                colors: pixels.clone(),
+                exhaustive: true,
+            }),
+            &mut rng,
+        );
+        let mut colors = drain(image.as_mut(), &mut rng);
+        colors.sort_by_key(|color| color[0]);
+        assert_eq!(colors, pixels);
+
+        // sampling at random never runs out
+        let mut sampled = color_source(
+            &ColorConfig::Image(ImageOptions {
+                colors: pixels.clone(),
+                exhaustive: false,
+            }),
+            &mut rng,
+        );
+        assert_eq!(sampled.remaining(), None);
+        for _ in 0..100 {
+            let color = rgb16_to_rgb8(sampled.next_color(&mut rng).unwrap());
+            assert!(pixels.contains(&color));
+        }
+    }
+}
@@ -0,0 +1,1359 @@
+use crate::animation::{AnimationFormat, AnimationOptions};
+use crate::checkpoint::CheckpointOptions;
+use crate::colors::{
+    ChannelOptions, ColorConfig, ColorOptions, ColorSpace, ImageOptions, PaletteOptions,
+    PaletteOrder, ReferenceColor, ReferenceOptions, SpreadSpace,
+};
+use crate::metrics::{DistanceMetric, PlacementMetric};
+use crate::neighborhood::{Falloff, Neighborhood, MAX_KERNEL_SIZE};
+use crate::output::{OutputFormat, OutputOptions};
+use crate::seeds::{SeedColors, SeedEntry, SeedPattern};
+use crate::spatial_index::ColorIndex;
+use crate::Coordinate;
+use image::Rgb;
+use std::{collections::HashMap, error::Error, fmt, fs, str::FromStr, time::Duration};
+use strict_yaml_rust::strict_yaml::Hash;
+use strict_yaml_rust::{StrictYaml, StrictYamlLoader};
+
+// placement indexes are stored as u32 with u32::MAX marking unplaced locations
+pub const MAX_PIXEL_COUNT: u64 = u32::MAX as u64;
+
+// everything that can be wrong with a config, keys are dot separated paths relative to the
+// "config" block (list entries are addressed by index)
+#[derive(Debug, PartialEq)]
+pub enum ConfigError {
+    Read {
+        path: String,
+        message: String,
+    },
+    Parse {
+        path: String,
+        message: String,
+    },
+    Missing {
+        key: String,
+    },
+    InvalidValue {
+        key: String,
+        value: String,
+        expected: String,
+    },
+    ZeroSize {
+        key: String,
+    },
+    ClampRange {
+        key: String,
+        min: f32,
+        max: f32,
+    },
+    OutOfBounds {
+        key: String,
+        location: (u32, u32),
+        canvas: (u32, u32),
+    },
+    DuplicateLocation {
+        key: String,
+        location: (u32, u32),
+        first_key: String,
+    },
+    Override {
+        key: String,
+        message: String,
+    },
+    ReadImage {
+        key: String,
+        path: String,
+        message: String,
+    },
+    TooManyPixels {
+        key: String,
+        canvas: (u32, u32),
+        max: u64,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Read { path, message } => {
+                write!(f, "unable to read config file {}: {}", path, message)
+            }
+            ConfigError::Parse { path, message } => {
+                write!(f, "unable to parse config file {}: {}", path, message)
+            }
+            ConfigError::Missing { key } => write!(f, "missing config key {}", key),
+            ConfigError::InvalidValue {
+                key,
+                value,
+                expected,
+            } => write!(
+                f,
+                "{} = \"{}\" is invalid, expected {}",
+                key, value, expected
+            ),
+            ConfigError::ZeroSize { key } => write!(f, "{} must be greater than zero", key),
+            ConfigError::ClampRange { key, min, max } => {
+                write!(f, "{} has min {} greater than max {}", key, min, max)
+            }
+            ConfigError::OutOfBounds {
+                key,
+                location,
+                canvas,
+            } => write!(
+                f,
+                "{} = ({}, {}) is outside the {}x{} canvas",
+                key, location.0, location.1, canvas.0, canvas.1
+            ),
+            ConfigError::DuplicateLocation {
+                key,
+                location,
+                first_key,
+            } => write!(
+                f,
+                "{} = ({}, {}) is the same location as {}",
+                key, location.0, location.1, first_key
+            ),
+            ConfigError::Override { key, message } => {
+                write!(f, "unable to override {}: {}", key, message)
+            }
+            ConfigError::ReadImage { key, path, message } => {
+                write!(f, "{}: unable to read image {}: {}", key, path, message)
+            }
+            ConfigError::TooManyPixels { key, canvas, max } => write!(
+                f,
+                "{} = {}x{} has more than the {} supported pixels",
+                key, canvas.0, canvas.1, max
+            ),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+// canvas dimensions, "auto" fits the canvas to a finite palette
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum CanvasSize {
+    Fixed { x_size: u32, y_size: u32 },
+    Auto,
+}
+
+// a rectangle of the canvas that is never painted
+#[derive(Copy, Clone)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// how the best position for a color is found
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SelectorKind {
+    BruteForce,
+    Indexed,
+}
+
+#[derive(Clone)]
+pub struct CanvasConfig {
+    pub size: CanvasSize,
+    // explicit starting locations (as points, in config order) followed by seed patterns
+    pub seeds: Vec<SeedEntry>,
+    pub blocked_regions: Vec<Region>,
+}
+
+#[derive(Clone)]
+pub struct PlacementConfig {
+    pub metric: PlacementMetric,
+    pub distance: DistanceMetric,
+    pub selector: SelectorKind,
+    // the locations a placed pixel adds to the boundry region
+    pub neighborhood: Neighborhood,
+    // the painted locations a boundry region location is scored against
+    pub scoring_neighborhood: Neighborhood,
+}
+
+// the typed contents of a config file
+pub struct Config {
+    pub seed: Option<u64>,
+    pub canvas: CanvasConfig,
+    pub colors: ColorConfig,
+    // every region grows only from the colors of the seed entry it started from
+    pub region_colors: bool,
+    pub placement: PlacementConfig,
+    // recording of the growth, None unless an animation format is configured
+    pub animation: Option<AnimationOptions>,
+    pub output: OutputOptions,
+    // periodic checkpoints, None unless an interval is configured
+    pub checkpoint: Option<CheckpointOptions>,
+}
+
+// read and parse a config file, returning its "config" block
+pub fn load_config(path: &str) -> Result<StrictYaml, ConfigError> {
+    // read config file into a string
+    let config_string = fs::read_to_string(path).map_err(|error| ConfigError::Read {
+        path: String::from(path),
+        message: error.to_string(),
+    })?;
+    return parse_config(&config_string, path);
+}
+
+// parse the text of a config file, path is only used to report errors
+pub fn parse_config(config_string: &str, path: &str) -> Result<StrictYaml, ConfigError> {
+    // parse config string using yaml structure
+    let documents =
+        StrictYamlLoader::load_from_str(config_string).map_err(|error| ConfigError::Parse {
+            path: String::from(path),
+            message: error.to_string(),
+        })?;
+    let config = documents
+        .first()
+        .map(|document| document["config"].clone())
+        .unwrap_or(StrictYaml::BadValue);
+    if config.is_badvalue() {
+        return Err(ConfigError::Missing {
+            key: String::from("config"),
+        });
+    }
+    return Ok(config);
+}
+
+// set the value at a dot separated key path, creating missing maps and lists along the way,
+// a list index equal to the list length appends a new entry
+pub fn apply_override(config: &mut StrictYaml, key_path: &str, value: &str) -> Result<(), String> {
+    let mut node = config;
+    for key in key_path.split('.') {
+        if key.is_empty() {
+            return Err(format!("empty key in {}", key_path));
+        }
+
+        // missing keys start out as empty lists when addressed by index, empty maps otherwise
+        if node.is_badvalue() {
+            *node = match key.parse::<usize>() {
+                Ok(_) => StrictYaml::Array(Vec::new()),
+                Err(_) => StrictYaml::Hash(Hash::new()),
+            };
+        }
+
+        node = match node {
+            StrictYaml::Hash(hash) => hash
+                .entry(StrictYaml::String(String::from(key)))
+                .or_insert(StrictYaml::BadValue),
+            StrictYaml::Array(array) => {
+                let index = key
+                    .parse::<usize>()
+                    .map_err(|_| format!("{} in {} is a list, expected an index", key, key_path))?;
+                if index == array.len() {
+                    array.push(StrictYaml::BadValue);
+                }
+                array
+                    .get_mut(index)
+                    .ok_or_else(|| format!("index {} in {} is out of range", index, key_path))?
+            }
+            StrictYaml::String(_) => {
+                return Err(format!("{} in {} is a value, not a map", key, key_path));
+            }
+            StrictYaml::BadValue => unreachable!(),
+        };
+    }
+
+    *node = StrictYaml::String(String::from(value));
+    return Ok(());
+}
+
+impl Config {
+    // convert the "config" block into typed values and check them
+    pub fn from_yaml(yaml: &StrictYaml) -> Result<Config, ConfigError> {
+        let root = Node {
+            value: yaml,
+            key: String::new(),
+        };
+
+        let seed = match root.get("seed").optional_str()? {
+            Some("random") | None => None,
+            Some(_) => Some(root.get("seed").parse::<u64>("an integer or \"random\"")?),
+        };
+
+        let config = Config {
+            seed,
+            canvas: parse_canvas(&root.get("canvas"))?,
+            colors: parse_colors(&root.get("colors"))?,
+            region_colors: root
+                .get("colors")
+                .get("per_region")
+                .parse_or(false, "true or false")?,
+            placement: parse_placement(&root.get("placement"))?,
+            animation: parse_animation(&root.get("animation"))?,
+            output: parse_output(&root.get("output"))?,
+            checkpoint: parse_checkpoint(&root.get("checkpoint"))?,
+        };
+        check_painting(&config.canvas, config.colors.is_finite())?;
+        check_colors(&config.colors, "colors")?;
+        check_selector(&config.placement)?;
+        return Ok(config);
+    }
+}
+
+// checks that need more than one value, location bounds are checked against the final canvas
+// size by check_starting_locations
+pub fn check_painting(canvas: &CanvasConfig, finite_colors: bool) -> Result<(), ConfigError> {
+    if let CanvasSize::Fixed { x_size, y_size } = canvas.size {
+        check_canvas_size(x_size, y_size)?;
+    }
+
+    // an automatic canvas size needs finite colors to fit
+    if canvas.size == CanvasSize::Auto && !finite_colors {
+        return Err(ConfigError::InvalidValue {
+            key: String::from("canvas.size"),
+            value: String::from("auto"),
+            expected: String::from(
+                "fixed sizes unless the colors are a palette, a sorted palette or an exhaustive image",
+            ),
+        });
+    }
+
+    // explicit points come first, each pattern is keyed by its place among the patterns
+    let point_count = starting_locations(&canvas.seeds).count();
+    for (index, seed) in canvas.seeds.iter().enumerate() {
+        let key = match seed.pattern {
+            SeedPattern::Point(_) => format!("canvas.starting_locations.{}", index),
+            _ => format!("canvas.seeds.{}", index - point_count),
+        };
+        check_seed_pattern(&seed.pattern, &key)?;
+        if let Some(colors) = &seed.colors.generator {
+            check_colors(colors, &format!("{}.colors", key))?;
+        }
+    }
+
+    // every starting location must be unique
+    let mut seen: HashMap<(u32, u32), usize> = HashMap::new();
+    for (index, location) in starting_locations(&canvas.seeds).enumerate() {
+        if let Some(first) = seen.insert((location.x, location.y), index) {
+            return Err(ConfigError::DuplicateLocation {
+                key: format!("canvas.starting_locations.{}", index),
+                location: (location.x, location.y),
+                first_key: format!("canvas.starting_locations.{}", first),
+            });
+        }
+    }
+
+    return Ok(());
+}
+
+// the explicit starting locations among the seed entries, in config order
+fn starting_locations(seeds: &[SeedEntry]) -> impl Iterator<Item = &Coordinate> {
+    return seeds.iter().filter_map(|seed| match &seed.pattern {
+        SeedPattern::Point(location) => Some(location),
+        _ => None,
+    });
+}
+
+// the ranges a seed pattern needs to generate its points, a zero spacing would never advance
+fn check_seed_pattern(pattern: &SeedPattern, key: &str) -> Result<(), ConfigError> {
+    match *pattern {
+        SeedPattern::Grid {
+            spacing_x,
+            spacing_y,
+            ..
+        } => {
+            check_nonzero(spacing_x, &format!("{}.spacing", key))?;
+            check_nonzero(spacing_y, &format!("{}.spacing_y", key))?;
+        }
+        SeedPattern::Edges { spacing } => check_nonzero(spacing, &format!("{}.spacing", key))?,
+        SeedPattern::Ellipse {
+            radius_x, radius_y, ..
+        } => {
+            for (radius, name) in [(radius_x, "radius_x"), (radius_y, "radius_y")] {
+                // without a radius every point would collapse onto the center
+                if radius.is_nan() || radius <= 0f32 {
+                    return Err(invalid_value(
+                        format!("{}.{}", key, name),
+                        radius,
+                        "a positive number",
+                    ));
+                }
+            }
+        }
+        SeedPattern::PoissonDisk { min_distance, .. } => {
+            check_range(
+                min_distance,
+                1f32,
+                f32::MAX,
+                &format!("{}.min_distance", key),
+            )?;
+        }
+        _ => {}
+    }
+    return Ok(());
+}
+
+// the ranges color generation needs, key is the path of the color block
+pub fn check_colors(colors: &ColorConfig, key: &str) -> Result<(), ConfigError> {
+    match colors {
+        ColorConfig::Random(options) => check_color_options(options, key)?,
+        ColorConfig::Reference(options) => {
+            if options.references.is_empty() {
+                return Err(invalid_value(
+                    format!("{}.reference_colors", key),
+                    "<empty list>",
+                    "at least one reference color",
+                ));
+            }
+            for (index, reference) in options.references.iter().enumerate() {
+                let weight_key = format!("{}.reference_colors.{}.weight", key, index);
+                check_range(reference.weight, 0f32, f32::MAX, &weight_key)?;
+            }
+            // references are chosen in proportion to their weight, so some must be above zero
+            let total_weight: f32 = options.references.iter().map(|r| r.weight).sum();
+            if !(total_weight > 0f32 && total_weight.is_finite()) {
+                return Err(invalid_value(
+                    format!("{}.reference_colors", key),
+                    format!("total weight {}", total_weight),
+                    "weights adding up to more than zero",
+                ));
+            }
+        }
+        ColorConfig::Palette(options) | ColorConfig::SortedPalette(options, _) => {
+            check_color_options(&options.color_options, key)?;
+            let palette_key = format!("{}.palette", key);
+            check_range(
+                options.bit_depth,
+                1,
+                8,
+                &format!("{}.bit_depth", palette_key),
+            )?;
+            check_range(
+                options.group_by_channel,
+                1,
+                3,
+                &format!("{}.group_by_channel", palette_key),
+            )?;
+        }
+        ColorConfig::Image(options) => {
+            if options.colors.is_empty() {
+                return Err(invalid_value(
+                    format!("{}.image.path", key),
+                    "<no pixels>",
+                    "an image with at least one pixel",
+                ));
+            }
+        }
+    }
+    return Ok(());
+}
+
+fn check_color_options(options: &ColorOptions, key: &str) -> Result<(), ConfigError> {
+    for (index, channel) in options.channels.iter().enumerate() {
+        if channel.min.is_nan() || channel.max.is_nan() || channel.min > channel.max {
+            return Err(ConfigError::ClampRange {
+                key: format!("{}.color_channel_options.channel_{}.clamp", key, index + 1),
+                min: channel.min,
+                max: channel.max,
+            });
+        }
+    }
+    return Ok(());
+}
+
+fn check_nonzero(value: u32, key: &str) -> Result<(), ConfigError> {
+    if value == 0 {
+        return Err(ConfigError::ZeroSize {
+            key: String::from(key),
+        });
+    }
+    return Ok(());
+}
+
+// NaN fails both comparisons, so it is rejected as well
+fn check_range<T: PartialOrd + fmt::Display>(
+    value: T,
+    min: T,
+    max: T,
+    key: &str,
+) -> Result<(), ConfigError> {
+    if !(value >= min && value <= max) {
+        return Err(invalid_value(
+            String::from(key),
+            value,
+            &format!("a value between {} and {}", min, max),
+        ));
+    }
+    return Ok(());
+}
+
+fn invalid_value(key: String, value: impl fmt::Display, expected: &str) -> ConfigError {
+    return ConfigError::InvalidValue {
+        key,
+        value: value.to_string(),
+        expected: String::from(expected),
+    };
+}
+
+// the built-in selector must be able to answer the placement settings
+pub fn check_selector(placement: &PlacementConfig) -> Result<(), ConfigError> {
+    // the indexed selector can only answer min queries for euclidean like distances
+    if placement.selector == SelectorKind::Indexed
+        && (placement.metric != PlacementMetric::Min || !ColorIndex::supports(placement.distance))
+    {
+        return Err(ConfigError::InvalidValue {
+            key: String::from("placement.selector"),
+            value: String::from("indexed"),
+            expected: String::from(
+                "brute_force unless the metric is min and the distance is rgb, lab76 or oklab",
+            ),
+        });
+    }
+
+    // the indexed selector only knows boundry region locations with a painted scoring neighbor,
+    // which the pixel a location grew from must be
+    if placement.selector == SelectorKind::Indexed
+        && !placement
+            .scoring_neighborhood
+            .reaches_back_along(&placement.neighborhood)
+    {
+        return Err(ConfigError::InvalidValue {
+            key: String::from("placement.selector"),
+            value: String::from("indexed"),
+            expected: String::from(
+                "brute_force unless the scoring neighborhood holds the mirror of every neighborhood offset",
+            ),
+        });
+    }
+
+    return Ok(());
+}
+
+// make sure every location of a canvas of the given size can be indexed
+pub fn check_canvas_size(x_size: u32, y_size: u32) -> Result<(), ConfigError> {
+    if x_size as u64 * y_size as u64 > MAX_PIXEL_COUNT {
+        return Err(ConfigError::TooManyPixels {
+            key: String::from("canvas.size"),
+            canvas: (x_size, y_size),
+            max: MAX_PIXEL_COUNT,
+        });
+    }
+    return Ok(());
+}
+
+// make sure every explicit starting location lies on a canvas of the given size
+pub fn check_starting_locations(
+    seeds: &[SeedEntry],
+    x_size: u32,
+    y_size: u32,
+) -> Result<(), ConfigError> {
+    for (index, location) in starting_locations(seeds).enumerate() {
+        if location.x >= x_size || location.y >= y_size {
+            return Err(ConfigError::OutOfBounds {
+                key: format!("canvas.starting_locations.{}", index),
+                location: (location.x, location.y),
+                canvas: (x_size, y_size),
+            });
+        }
+    }
+    return Ok(());
+}
+
+fn parse_canvas(canvas: &Node) -> Result<CanvasConfig, ConfigError> {
+    // canvas size, both dimensions "auto" or both numbers
+    let size_x = canvas.get("size").get("x");
+    let size_y = canvas.get("size").get("y");
+    let size = match (size_x.str()?, size_y.str()?) {
+        ("auto", "auto") => CanvasSize::Auto,
+        _ => CanvasSize::Fixed {
+            x_size: size_x.parse_nonzero()?,
+            y_size: size_y.parse_nonzero()?,
+        },
+    };
+
+    let mut seeds = Vec::new();
+    for location in canvas.get("starting_locations").optional_list()? {
+        seeds.push(SeedEntry {
+            pattern: SeedPattern::Point(Coordinate {
+                x: location.get("x").parse::<u32>("a non-negative integer")?,
+                y: location.get("y").parse::<u32>("a non-negative integer")?,
+            }),
+            colors: parse_seed_colors(&location)?,
+        });
+    }
+    for seed in canvas.get("seeds").optional_list()? {
+        seeds.push(SeedEntry {
+            pattern: parse_seed_pattern(&seed)?,
+            colors: parse_seed_colors(&seed)?,
+        });
+    }
+    if seeds.is_empty() {
+        return Err(ConfigError::Missing {
+            key: canvas.get("starting_locations").key,
+        });
+    }
+
+    let mut blocked_regions = Vec::new();
+    for region in canvas.get("blocked_regions").optional_list()? {
+        blocked_regions.push(Region {
+            x: region.get("x").parse::<u32>("a non-negative integer")?,
+            y: region.get("y").parse::<u32>("a non-negative integer")?,
+            width: region.get("width").parse_nonzero()?,
+            height: region.get("height").parse_nonzero()?,
+        });
+    }
+
+    return Ok(CanvasConfig {
+        size,
+        seeds,
+        blocked_regions,
+    });
+}
+
+// an optional fixed color and an optional color block of the same form as "colors"
+fn parse_seed_colors(seed: &Node) -> Result<SeedColors, ConfigError> {
+    let fixed_color = match seed.get("color").value {
+        StrictYaml::BadValue => None,
+        _ => Some(parse_rgb(&seed.get("color"))?),
+    };
+    let generator = match seed.get("colors").value {
+        StrictYaml::BadValue => None,
+        _ => Some(parse_colors(&seed.get("colors"))?),
+    };
+    return Ok(SeedColors {
+        fixed_color,
+        generator,
+    });
+}
+
+fn parse_rgb(color: &Node) -> Result<Rgb<u8>, ConfigError> {
+    let channel = |key: &str| color.get(key).parse::<u8>("an integer between 0 and 255");
+    return Ok(Rgb([channel("r")?, channel("g")?, channel("b")?]));
+}
+
+fn parse_seed_pattern(seed: &Node) -> Result<SeedPattern, ConfigError> {
+    let pattern = seed.get("pattern");
+    let number = |key: &str| seed.get(key).parse::<f32>("a number");
+    let count = || seed.get("count").parse::<u32>("a non-negative integer");
+    match pattern.str()? {
+        "grid" => {
+            let spacing_x = seed.get("spacing").parse::<u32>("a positive integer")?;
+            let spacing_y = seed
+                .get("spacing_y")
+                .parse_or(spacing_x, "a positive integer")?;
+            return Ok(SeedPattern::Grid {
+                spacing_x,
+                spacing_y,
+                offset_x: seed
+                    .get("offset_x")
+                    .parse_or(spacing_x / 2, "a non-negative integer")?,
+                offset_y: seed
+                    .get("offset_y")
+                    .parse_or(spacing_y / 2, "a non-negative integer")?,
+            });
+        }
+        "random" => return Ok(SeedPattern::Random { count: count()? }),
+        "circle" | "ellipse" => {
+            // "radius" sets both radii, radius_x and radius_y override it
+            let radius = |key: &str| match seed.get(key).optional_str()? {
+                Some(_) => seed.get(key).parse::<f32>("a positive number"),
+                None => seed.get("radius").parse::<f32>("a positive number"),
+            };
+            let center = match seed.get("center_x").optional_str()? {
+                Some(_) => Some((number("center_x")?, number("center_y")?)),
+                None => None,
+            };
+            return Ok(SeedPattern::Ellipse {
+                center,
+                radius_x: radius("radius_x")?,
+                radius_y: radius("radius_y")?,
+                count: count()?,
+            });
+        }
+        "line" => {
+            return Ok(SeedPattern::Line {
+                start: (number("start_x")?, number("start_y")?),
+                end: (number("end_x")?, number("end_y")?),
+                count: count()?,
+            });
+        }
+        "poisson_disk" => {
+            return Ok(SeedPattern::PoissonDisk {
+                min_distance: seed
+                    .get("min_distance")
+                    .parse::<f32>("a number of at least 1")?,
+                attempts: seed
+                    .get("attempts")
+                    .parse_or(30u32, "a non-negative integer")?,
+            });
+        }
+        "center" => return Ok(SeedPattern::Center),
+        "edges" => {
+            return Ok(SeedPattern::Edges {
+                spacing: seed.get("spacing").parse::<u32>("a positive integer")?,
+            });
+        }
+        "corners" => return Ok(SeedPattern::Corners),
+        _ => {
+            return Err(pattern.invalid(
+                "one of: grid, random, circle, ellipse, line, poisson_disk, center, edges, corners",
+            ))
+        }
+    }
+}
+
+fn parse_colors(colors: &Node) -> Result<ColorConfig, ConfigError> {
+    let mode = colors.get("generation_mode");
+    match mode.optional_str()?.unwrap_or("random") {
+        "random" => return Ok(ColorConfig::Random(parse_color_options(colors)?)),
+        "reference" => return Ok(ColorConfig::Reference(parse_reference_options(colors)?)),
+        "palette" => return Ok(ColorConfig::Palette(parse_palette_options(colors)?)),
+        "sorted_palette" => {
+            let sort_by = colors.get("palette").get("sort_by");
+            let order = PaletteOrder::from_name(sort_by.optional_str()?.unwrap_or("hue"))
+                .ok_or_else(|| sort_by.invalid("one of: hue, saturation, lightness"))?;
+            return Ok(ColorConfig::SortedPalette(
+                parse_palette_options(colors)?,
+                order,
+            ));
+        }
+        "image" => {
+            return Ok(ColorConfig::Image(parse_image_options(
+                &colors.get("image"),
+            )?))
+        }
+        _ => return Err(mode.invalid("one of: random, reference, palette, sorted_palette, image")),
+    }
+}
+
+fn parse_palette_options(colors: &Node) -> Result<PaletteOptions, ConfigError> {
+    let palette = colors.get("palette");
+    return Ok(PaletteOptions {
+        color_options: parse_color_options(colors)?,
+        bit_depth: palette
+            .get("bit_depth")
+            .parse_or(8u32, "an integer between 1 and 8")?,
+        group_by_channel: palette
+            .get("group_by_channel")
+            .parse_or(1usize, "1, 2 or 3")?,
+        shuffle: palette.get("shuffle").parse_or(true, "true or false")?,
+    });
+}
+
+// read the pixels of the image colors are sampled from
+fn parse_image_options(image: &Node) -> Result<ImageOptions, ConfigError> {
+    let path_node = image.get("path");
+    let path = path_node.str()?;
+    let pixels = image::open(path)
+        .map_err(|error| ConfigError::ReadImage {
+            key: path_node.key.clone(),
+            path: String::from(path),
+            message: error.to_string(),
+        })?
+        .to_rgb();
+    return Ok(ImageOptions {
+        colors: pixels.pixels().copied().collect(),
+        exhaustive: image.get("exhaustive").parse_or(false, "true or false")?,
+    });
+}
+
+fn parse_color_options(colors: &Node) -> Result<ColorOptions, ConfigError> {
+    let color_space = colors.get("generation_color_space");
+    let channel_config = colors.get("color_channel_options");
+    return Ok(ColorOptions {
+        color_space: ColorSpace::from_name(color_space.str()?)
+            .ok_or_else(|| color_space.invalid("one of: rgb, hsv, hsl, hwb"))?,
+        channels: [
+            parse_channel_options(&channel_config.get("channel_1"))?,
+            parse_channel_options(&channel_config.get("channel_2"))?,
+            parse_channel_options(&channel_config.get("channel_3"))?,
+        ],
+    });
+}
+
+fn parse_channel_options(channel: &Node) -> Result<ChannelOptions, ConfigError> {
+    let min = channel.get("clamp").get("min").parse_or(0f32, "a number")?;
+    let max = channel.get("clamp").get("max").parse_or(1f32, "a number")?;
+
+    // a locked channel is held at "value", or at the clamp min if no value is given
+    let lock = match channel.get("lock").parse_or(false, "true or false")? {
+        true => Some(channel.get("value").parse_or(min, "a number")?),
+        false => None,
+    };
+
+    return Ok(ChannelOptions { lock, min, max });
+}
+
+fn parse_reference_options(colors: &Node) -> Result<ReferenceOptions, ConfigError> {
+    let mut references = Vec::new();
+    let reference_list = colors.get("reference_colors");
+    for reference in reference_list.list()? {
+        references.push(ReferenceColor {
+            color: parse_rgb(&reference)?,
+            weight: reference
+                .get("weight")
+                .parse_or(1f32, "a non-negative number")?,
+        });
+    }
+
+    let spread = colors.get("reference_spread");
+    let spread_space = spread.get("color_space");
+    return Ok(ReferenceOptions {
+        references,
+        spread_space: match spread_space.optional_str()? {
+            Some(name) => SpreadSpace::from_name(name)
+                .ok_or_else(|| spread_space.invalid("one of: rgb, lab"))?,
+            None => SpreadSpace::Rgb,
+        },
+        spread: [
+            spread.get("channel_1").parse_or(0f32, "a number")?,
+            spread.get("channel_2").parse_or(0f32, "a number")?,
+            spread.get("channel_3").parse_or(0f32, "a number")?,
+        ],
+    });
+}
+
+fn parse_placement(placement: &Node) -> Result<PlacementConfig, ConfigError> {
+    let neighborhood = parse_neighborhood(&placement.get("neighborhood"))?;
+    let metric = placement.get("metric");
+    let distance = placement.get("distance");
+    let selector = placement.get("selector");
+    return Ok(PlacementConfig {
+        metric: match metric.optional_str()? {
+            Some(name) => PlacementMetric::from_name(name).ok_or_else(|| {
+                metric
+                    .invalid("one of: min, average, max, count_weighted, median, weighted_average")
+            })?,
+            None => PlacementMetric::Min,
+        },
+        distance: match distance.optional_str()? {
+            Some(name) => DistanceMetric::from_name(name)
+                .ok_or_else(|| distance.invalid("one of: rgb, lab76, ciede2000, oklab"))?,
+            None => DistanceMetric::Rgb,
+        },
+        selector: match selector.optional_str()?.unwrap_or("brute_force") {
+            "brute_force" => SelectorKind::BruteForce,
+            "indexed" => SelectorKind::Indexed,
+            _ => return Err(selector.invalid("one of: brute_force, indexed")),
+        },
+        scoring_neighborhood: match placement.get("scoring_neighborhood").value {
+            StrictYaml::BadValue => neighborhood.clone(),
+            _ => parse_neighborhood(&placement.get("scoring_neighborhood"))?,
+        },
+        neighborhood,
+    });
+}
+
+fn parse_neighborhood(neighborhood: &Node) -> Result<Neighborhood, ConfigError> {
+    let shape = neighborhood.get("shape");
+    let weighting = neighborhood.get("weighting");
+    let shaped = match shape.optional_str()?.unwrap_or("moore") {
+        "von_neumann" => Neighborhood::von_neumann(),
+        "moore" => Neighborhood::moore(),
+        "disc" => {
+            let radius = neighborhood.get("radius");
+            let max_radius = (MAX_KERNEL_SIZE / 2) as u32;
+            Neighborhood::disc(
+                radius
+                    .parse::<u32>(&format!("an integer between 1 and {}", max_radius))
+                    .and_then(|value| radius.check_range(value, 1, max_radius))?,
+            )
+        }
+        "kernel" => {
+            // kernels carry their own weights
+            if weighting.optional_str()?.is_some() {
+                return Err(weighting.invalid("unset for kernels, their values are the weights"));
+            }
+            let kernel = neighborhood.get("kernel");
+            let mut rows: Vec<Vec<f32>> = Vec::new();
+            for row in kernel.list()? {
+                let values: Result<Vec<f32>, _> =
+                    row.str()?.split_whitespace().map(f32::from_str).collect();
+                rows.push(values.map_err(|_| row.invalid("numbers separated by spaces"))?);
+            }
+            return Neighborhood::kernel(&rows).map_err(|expected| ConfigError::InvalidValue {
+                key: kernel.key.clone(),
+                value: format!("{} rows", rows.len()),
+                expected,
+            });
+        }
+        _ => return Err(shape.invalid("one of: von_neumann, moore, disc, kernel")),
+    };
+
+    let falloff = match weighting.optional_str()?.unwrap_or("uniform") {
+        "uniform" => Falloff::Uniform,
+        "inverse_distance" => Falloff::InverseDistance,
+        "gaussian" => {
+            let sigma = neighborhood.get("sigma");
+            let value = sigma.parse_or(1f32, "a positive number")?;
+            if value.is_nan() || value <= 0f32 {
+                return Err(sigma.invalid("a positive number"));
+            }
+            Falloff::Gaussian(value)
+        }
+        _ => return Err(weighting.invalid("one of: uniform, inverse_distance, gaussian")),
+    };
+    return Ok(shaped.with_falloff(falloff));
+}
+
+fn parse_animation(animation: &Node) -> Result<Option<AnimationOptions>, ConfigError> {
+    // a comma separated list of formats
+    let format = animation.get("format");
+    let mut formats: Vec<AnimationFormat> = Vec::new();
+    for name in format.optional_str()?.unwrap_or("none").split(',') {
+        match name.trim() {
+            "none" => continue,
+            name => formats.push(AnimationFormat::from_name(name).ok_or_else(|| {
+                format.invalid("a comma separated list of: none, gif, apng, frames, y4m")
+            })?),
+        }
+    }
+    if formats.is_empty() {
+        return Ok(None);
+    }
+    let frame_width = match animation.get("frame_width").optional_str()? {
+        Some(_) => Some(animation.get("frame_width").parse_nonzero()?),
+        None => None,
+    };
+    return Ok(Some(AnimationOptions {
+        formats,
+        frame_interval: animation.get("frame_interval").parse_nonzero()? as u64,
+        frame_width,
+        frame_delay_ms: animation
+            .get("frame_delay_ms")
+            .parse_or(40u32, "a non-negative integer")?,
+        final_hold_ms: animation
+            .get("final_hold_ms")
+            .parse_or(2000u32, "a non-negative integer")?,
+        y4m_output: String::from(
+            animation
+                .get("y4m_output")
+                .optional_str()?
+                .unwrap_or("growth.y4m"),
+        ),
+    }));
+}
+
+// the painting is written as an 8-bit PNG named painting.png unless configured otherwise
+fn parse_output(output: &Node) -> Result<OutputOptions, ConfigError> {
+    let mut options = OutputOptions::default();
+    let format = output.get("format");
+    if let Some(name) = format.optional_str()? {
+        options.format = OutputFormat::from_name(name)
+            .ok_or_else(|| format.invalid("png, tiff, webp, bmp, ppm or qoi"))?;
+    }
+
+    let bit_depth = output.get("bit_depth");
+    options.bit_depth = bit_depth.parse_or(8u8, "8 or 16")?;
+    if options.bit_depth != 8 && options.bit_depth != 16 {
+        return Err(bit_depth.invalid("8 or 16"));
+    }
+    if options.bit_depth == 16 && !options.format.supports_16_bit() {
+        return Err(bit_depth.invalid("8 (16 bits per channel need png, tiff or ppm)"));
+    }
+
+    let file_name = output.get("file_name");
+    if let Some(name) = file_name.optional_str()? {
+        if name.trim().is_empty() {
+            return Err(file_name.invalid("a file name"));
+        }
+        options.file_name = String::from(name);
+    }
+    return Ok(options);
+}
+
+// checkpoints are only written when an interval is given or on_stop is set
+fn parse_checkpoint(checkpoint: &Node) -> Result<Option<CheckpointOptions>, ConfigError> {
+    let interval_seconds: u64 = checkpoint
+        .get("interval_seconds")
+        .parse_or(0u64, "a non-negative integer")?;
+    let on_stop: bool = checkpoint.get("on_stop").parse_or(false, "true or false")?;
+    if interval_seconds == 0 && !on_stop {
+        return Ok(None);
+    }
+    return Ok(Some(CheckpointOptions {
+        interval: match interval_seconds {
+            0 => None,
+            seconds => Some(Duration::from_secs(seconds)),
+        },
+        on_stop,
+        file_name: String::from(
+            checkpoint
+                .get("file_name")
+                .optional_str()?
+                .unwrap_or("checkpoint.bin"),
+        ),
+    }));
+}
+
+// a value in the config tree along with its key path, used to report errors
+struct Node<'a> {
+    value: &'a StrictYaml,
+    key: String,
+}
+
+impl<'a> Node<'a> {
+    fn get(&self, key: &str) -> Node<'a> {
+        return Node {
+            value: &self.value[key],
+            key: match self.key.is_empty() {
+                true => String::from(key),
+                false => format!("{}.{}", self.key, key),
+            },
+        };
+    }
+
+    // a printable form of the value for error messages
+    fn display_value(&self) -> String {
+        match self.value {
+            StrictYaml::String(value) => value.clone(),
+            StrictYaml::Array(_) => String::from("<list>"),
+            StrictYaml::Hash(_) => String::from("<map>"),
+            StrictYaml::BadValue => String::new(),
+        }
+    }
+
+    fn invalid(&self, expected: &str) -> ConfigError {
+        return ConfigError::InvalidValue {
+            key: self.key.clone(),
+            value: self.display_value(),
+            expected: String::from(expected),
+        };
+    }
+
+    fn optional_str(&self) -> Result<Option<&'a str>, ConfigError> {
+        match self.value {
+            StrictYaml::BadValue => return Ok(None),
+            StrictYaml::String(value) => return Ok(Some(value.as_str())),
+            _ => return Err(self.invalid("a single value")),
+        }
+    }
+
+    fn str(&self) -> Result<&'a str, ConfigError> {
+        return self.optional_str()?.ok_or_else(|| ConfigError::Missing {
+            key: self.key.clone(),
+        });
+    }
+
+    fn parse<T: FromStr>(&self, expected: &str) -> Result<T, ConfigError> {
+        return self
+            .str()?
+            .trim()
+            .parse::<T>()
+            .map_err(|_| self.invalid(expected));
+    }
+
+    fn parse_or<T: FromStr>(&self, default: T, expected: &str) -> Result<T, ConfigError> {
+        match self.optional_str()? {
+            Some(_) => return self.parse::<T>(expected),
+            None => return Ok(default),
+        }
+    }
+
+    fn parse_nonzero(&self) -> Result<u32, ConfigError> {
+        let value = self.parse::<u32>("a positive integer")?;
+        if value == 0 {
+            return Err(ConfigError::ZeroSize {
+                key: self.key.clone(),
+            });
+        }
+        return Ok(value);
+    }
+
+    fn check_range<T: PartialOrd + fmt::Display>(
+        &self,
+        value: T,
+        min: T,
+        max: T,
+    ) -> Result<T, ConfigError> {
+        if value < min || value > max {
+            return Err(self.invalid(&format!("a value between {} and {}", min, max)));
+        }
+        return Ok(value);
+    }
+
+    fn list(&self) -> Result<Vec<Node<'a>>, ConfigError> {
+        match self.value {
+            StrictYaml::BadValue => Err(ConfigError::Missing {
+                key: self.key.clone(),
+            }),
+            _ => self.optional_list(),
+        }
+    }
+
+    // a missing list is treated as empty
+    fn optional_list(&self) -> Result<Vec<Node<'a>>, ConfigError> {
+        match self.value {
+            StrictYaml::BadValue => return Ok(Vec::new()),
+            StrictYaml::Array(entries) => {
+                return Ok(entries
+                    .iter()
+                    .enumerate()
+                    .map(|(index, value)| Node {
+                        value,
+                        key: format!("{}.{}", self.key, index),
+                    })
+                    .collect());
+            }
+            _ => return Err(self.invalid("a list")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(config_string: &str) -> Result<Config, ConfigError> {
+        let documents = StrictYamlLoader::load_from_str(config_string).unwrap();
+        return Config::from_yaml(&documents[0]["config"]);
+    }
+
+    // a minimal valid config with one extra line appended to the canvas block
+    fn canvas_config(size: (&str, &str), extra: &str) -> String {
+        return format!(
+            "config:
+  canvas:
+    size:
+      x: \"{}\"
+      y: \"{}\"
+    starting_locations:
+      - x: 0
+        y: 0
+{}
+  colors:
+    generation_mode: \"random\"
+    generation_color_space: \"rgb\"
+",
+            size.0, size.1, extra
+        );
+    }
+
+    #[test]
+    fn overrides_append_entries_and_create_keys() {
+        let mut yaml = parse_config(&canvas_config(("8", "8"), ""), "test").unwrap();
+        apply_override(&mut yaml, "canvas.starting_locations.1.x", "5").unwrap();
+        apply_override(&mut yaml, "canvas.starting_locations.1.y", "6").unwrap();
+        apply_override(&mut yaml, "placement.neighborhood.shape", "von_neumann").unwrap();
+        apply_override(&mut yaml, "canvas.size.x", "12").unwrap();
+
+        let config = Config::from_yaml(&yaml).unwrap();
+        assert_eq!(
+            config.canvas.size,
+            CanvasSize::Fixed {
+                x_size: 12,
+                y_size: 8
+            }
+        );
+        assert_eq!(
+            starting_locations(&config.canvas.seeds)
+                .map(|location| (location.x, location.y))
+                .collect::<Vec<_>>(),
+            vec![(0, 0), (5, 6)]
+        );
+        assert_eq!(config.placement.neighborhood, Neighborhood::von_neumann());
+    }
+
+    #[test]
+    fn rejects_bad_override_paths_and_values() {
+        let mut yaml = parse_config(&canvas_config(("8", "8"), ""), "test").unwrap();
+        assert!(apply_override(&mut yaml, "canvas..x", "1").is_err());
+        assert!(apply_override(&mut yaml, "canvas.starting_locations.x", "1").is_err());
+        assert!(apply_override(&mut yaml, "canvas.starting_locations.3.x", "1").is_err());
+        assert!(apply_override(&mut yaml, "canvas.size.x.value", "1").is_err());
+
+        // paths that exist still need valid values
+        apply_override(&mut yaml, "canvas.size.y", "tall").unwrap();
+        assert_eq!(
+            Config::from_yaml(&yaml).err(),
+            Some(ConfigError::InvalidValue {
+                key: String::from("canvas.size.y"),
+                value: String::from("tall"),
+                expected: String::from("a positive integer"),
+            })
+        );
+    }
+
+    #[test]
+    fn reports_key_path_and_value() {
+        assert_eq!(
+            parse(&canvas_config(("wide", "8"), "")).err(),
+            Some(ConfigError::InvalidValue {
+                key: String::from("canvas.size.x"),
+                value: String::from("wide"),
+                expected: String::from("a positive integer"),
+            })
+        );
+        assert_eq!(
+            parse(&canvas_config(("8", "0"), "")).err(),
+            Some(ConfigError::ZeroSize {
+                key: String::from("canvas.size.y"),
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_canvases_over_the_pixel_limit() {
+        assert_eq!(
+            parse(&canvas_config(("70000", "70000"), "")).err(),
+            Some(ConfigError::TooManyPixels {
+                key: String::from("canvas.size"),
+                canvas: (70000, 70000),
+                max: MAX_PIXEL_COUNT,
+            })
+        );
+        assert!(check_canvas_size(65535, 65537).is_ok());
+    }
+
+    #[test]
+    fn ellipses_need_a_radius() {
+        let ellipse = |radii: &str| {
+            let seeds = format!(
+                "    seeds:
+      - pattern: \"ellipse\"
+        count: 4
+{}",
+                radii
+            );
+            parse(&canvas_config(("8", "8"), &seeds))
+        };
+        assert_eq!(
+            ellipse("").err(),
+            Some(ConfigError::Missing {
+                key: String::from("canvas.seeds.0.radius"),
+            })
+        );
+        assert!(ellipse("        radius: 0\n").is_err());
+        assert!(ellipse("        radius_x: 3\n        radius_y: 2\n").is_ok());
+        let config = parse(&canvas_config(
+            ("8", "8"),
+            "    seeds:
+      - pattern: \"circle\"
+        count: 4
+        radius: 3
+        radius_y: 1.5
+",
+        ))
+        .unwrap();
+        assert!(matches!(
+            config.canvas.seeds[1].pattern,
+            SeedPattern::Ellipse {
+                radius_x: 3f32,
+                radius_y: 1.5f32,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn rejects_duplicate_starting_locations() {
+        let config_string = canvas_config(("8", "8"), "      - x: 0\n        y: 0");
+        assert_eq!(
+            parse(&config_string).err(),
+            Some(ConfigError::DuplicateLocation {
+                key: String::from("canvas.starting_locations.1"),
+                location: (0, 0),
+                first_key: String::from("canvas.starting_locations.0"),
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_out_of_bounds_starting_locations() {
+        let config = parse(&canvas_config(("8", "8"), "      - x: 3\n        y: 8")).unwrap();
+        assert_eq!(
+            check_starting_locations(&config.canvas.seeds, 8, 8),
+            Err(ConfigError::OutOfBounds {
+                key: String::from("canvas.starting_locations.1"),
+                location: (3, 8),
+                canvas: (8, 8),
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_references_without_weight() {
+        let references = |weights: [&str; 2]| {
+            let mut config_string = canvas_config(("8", "8"), "")
+                .replace("\"random\"", "\"reference\"")
+                + "    reference_colors:\n";
+            for weight in weights {
+                config_string += &format!(
+                    "      - r: 10\n        g: 20\n        b: 30\n        weight: {}\n",
+                    weight
+                );
+            }
+            return parse(&config_string);
+        };
+        assert!(references(["0", "0.5"]).is_ok());
+        assert_eq!(
+            references(["0", "0"]).err(),
+            Some(ConfigError::InvalidValue {
+                key: String::from("colors.reference_colors"),
+                value: String::from("total weight 0"),
+                expected: String::from("weights adding up to more than zero"),
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_inverted_clamp_range() {
+        let config_string = canvas_config(("8", "8"), "")
+            + "    color_channel_options:
+      channel_2:
+        clamp:
+          min: 0.8
+          max: 0.2
+";
+        assert_eq!(
+            parse(&config_string).err(),
+            Some(ConfigError::ClampRange {
+                key: String::from("colors.color_channel_options.channel_2.clamp"),
+                min: 0.8,
+                max: 0.2,
+            })
+        );
+    }
+
+    #[test]
+    fn parses_neighborhood_kernels() {
+        let placement = |kernel: &str| {
+            canvas_config(("8", "8"), "")
+                + "  placement:
+    scoring_neighborhood:
+      shape: \"kernel\"
+      kernel:
+" + kernel
+        };
+        let config = parse(&placement(
+            "        - \"1 0 0\"\n        - \"0 1 0\"\n        - \"0 0 2\"\n",
+        ))
+        .unwrap();
+        assert_eq!(config.placement.neighborhood, Neighborhood::moore());
+        assert_eq!(config.placement.scoring_neighborhood.len(), 2);
+        assert_eq!(
+            parse(&placement("        - \"1 1\"\n        - \"1 1\"\n")).err(),
+            Some(ConfigError::InvalidValue {
+                key: String::from("placement.scoring_neighborhood.kernel"),
+                value: String::from("2 rows"),
+                expected: String::from("an odd number of rows up to 15, got 2"),
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_16_bit_output_for_8_bit_formats() {
+        let output = |format: &str| {
+            canvas_config(("8", "8"), "")
+                + &format!(
+                    "  output:
+    format: \"{}\"
+    bit_depth: \"16\"
+",
+                    format
+                )
+        };
+        assert_eq!(parse(&output("tiff")).unwrap().output.bit_depth, 16);
+        assert_eq!(
+            parse(&output("webp")).err(),
+            Some(ConfigError::InvalidValue {
+                key: String::from("output.bit_depth"),
+                value: String::from("16"),
+                expected: String::from("8 (16 bits per channel need png, tiff or ppm)"),
+            })
+        );
+    }
+}
@@ -0,0 +1,1245 @@
+#![allow(clippy::needless_return)]
+
+pub mod animation;
+pub mod checkpoint;
+pub mod colors;
+pub mod config;
+pub mod metadata;
+pub mod metrics;
+pub mod neighborhood;
+mod npy;
+pub mod output;
+pub mod png_chunks;
+pub mod rng;
+pub mod seeds;
+pub mod selectors;
+mod spatial_index;
+
+use colors::{rgb16_to_rgb8, ChannelOptions, ColorConfig, ColorOptions, ColorSource, ColorSpace};
+use config::{
+    CanvasConfig, CanvasSize, Config, ConfigError, PlacementConfig, Region, SelectorKind,
+};
+use image::{Rgb, RgbImage};
+use metrics::{DistanceMetric, PlacementMetric};
+use neighborhood::Neighborhood;
+use output::Rgb16Image;
+use rand::{random, Rng};
+use rng::PaintRng;
+use seeds::{Seed, SeedColorSource, SeedColors, SeedEntry, SeedPattern};
+use selectors::{BruteForceSelector, Candidates, PositionSelector};
+use std::time::Instant;
+
+// the state of a painting in progress, built by PaintingBuilder
+pub struct Painter {
+    image: RgbImage,
+    // the painting at the full precision of the generated colors, only kept for 16-bit output
+    deep_image: Option<Rgb16Image>,
+    // the seed every random choice was drawn from
+    seed: u64,
+    occupancy: Vec<CellState>,
+    boundry_region_list: Vec<Coordinate>,
+    boundry_region_positions: Vec<usize>,
+    starting_locations: Vec<Seed>,
+    seed_color_sources: Vec<SeedColorSource>,
+    canvas_constraints: Constraints,
+    canvas_stats: Stats,
+    color_source: Box<dyn ColorSource>,
+    placement_metric: PlacementMetric,
+    distance_metric: DistanceMetric,
+    // the locations a placed pixel adds to the boundry region
+    growth_neighborhood: Neighborhood,
+    // the painted locations a boundry region location is scored against
+    scoring_neighborhood: Neighborhood,
+    color_cache: Vec<[f32; 3]>,
+    position_selector: Box<dyn PositionSelector>,
+    rng: PaintRng,
+    // the seed (index into starting_locations) every location grew from, NO_OWNER if none
+    owners: Vec<u32>,
+    region_frontier_counts: Vec<usize>,
+    region_colors: bool,
+    region_exhausted: Vec<bool>,
+    // when every location was painted, as a placement index (NOT_PLACED if never) and as
+    // seconds since the painting started
+    placement_order: Vec<u32>,
+    placement_times: Vec<f32>,
+}
+
+// owner of locations no seed has reached
+const NO_OWNER: u32 = u32::MAX;
+
+// placement index of locations that were never painted
+const NOT_PLACED: u32 = u32::MAX;
+
+// scoring neighborhoods up to this size (a radius 4 disc) keep their distances on the stack
+const STACK_NEIGHBORS: usize = 48;
+
+// state of a single canvas location
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum CellState {
+    Empty,
+    Frontier,
+    Filled,
+    Blocked,
+}
+struct Constraints {
+    x_size: u32,
+    y_size: u32,
+}
+pub struct Stats {
+    pub current_pixels_placed_count: u64,
+    pub start_time: Instant,
+}
+struct Pixel {
+    position: Coordinate,
+    color: Rgb<u8>,
+}
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Coordinate {
+    pub x: u32,
+    pub y: u32,
+}
+
+// everything a painting starts from, read from a config or set up in code
+pub struct PaintingBuilder {
+    canvas: CanvasConfig,
+    colors: ColorConfig,
+    region_colors: bool,
+    placement: PlacementConfig,
+    seed: Option<u64>,
+    deep_color: bool,
+    // replaces the shared colors when set
+    custom_color_source: Option<Box<dyn ColorSource>>,
+    // replaces the selector kind when set
+    custom_selector: Option<Box<dyn PositionSelector>>,
+}
+
+impl PaintingBuilder {
+    // a canvas without starting locations, painted with random colors from the whole RGB cube
+    pub fn new(x_size: u32, y_size: u32) -> PaintingBuilder {
+        let channel = ChannelOptions {
+            lock: None,
+            min: 0f32,
+            max: 1f32,
+        };
+        return PaintingBuilder {
+            canvas: CanvasConfig {
+                size: CanvasSize::Fixed { x_size, y_size },
+                seeds: Vec::new(),
+                blocked_regions: Vec::new(),
+            },
+            colors: ColorConfig::Random(ColorOptions {
+                color_space: ColorSpace::Rgb,
+                channels: [channel; 3],
+            }),
+            region_colors: false,
+            placement: PlacementConfig {
+                metric: PlacementMetric::Min,
+                distance: DistanceMetric::Rgb,
+                selector: SelectorKind::BruteForce,
+                neighborhood: Neighborhood::moore(),
+                scoring_neighborhood: Neighborhood::moore(),
+            },
+            seed: None,
+            deep_color: false,
+            custom_color_source: None,
+            custom_selector: None,
+        };
+    }
+
+    // everything the config sets up, the 16-bit image is kept for 16-bit output
+    pub fn from_config(config: &Config) -> PaintingBuilder {
+        return PaintingBuilder {
+            canvas: config.canvas.clone(),
+            colors: config.colors.clone(),
+            region_colors: config.region_colors,
+            placement: config.placement.clone(),
+            seed: config.seed,
+            deep_color: config.output.bit_depth == 16,
+            custom_color_source: None,
+            custom_selector: None,
+        };
+    }
+
+    pub fn size(mut self, x_size: u32, y_size: u32) -> PaintingBuilder {
+        self.canvas.size = CanvasSize::Fixed { x_size, y_size };
+        return self;
+    }
+
+    // size the canvas to fit a palette color source
+    pub fn fit_to_palette(mut self) -> PaintingBuilder {
+        self.canvas.size = CanvasSize::Auto;
+        return self;
+    }
+
+    // add a single starting location painted from the shared color source
+    pub fn starting_location(self, x: u32, y: u32) -> PaintingBuilder {
+        return self.seed_entry(SeedEntry {
+            pattern: SeedPattern::Point(Coordinate { x, y }),
+            colors: SeedColors::default(),
+        });
+    }
+
+    // add starting locations, explicit points keep their order ahead of every pattern
+    pub fn seed_entry(mut self, entry: SeedEntry) -> PaintingBuilder {
+        let points = self
+            .canvas
+            .seeds
+            .iter()
+            .take_while(|seed| matches!(seed.pattern, SeedPattern::Point(_)))
+            .count();
+        match entry.pattern {
+            SeedPattern::Point(_) => self.canvas.seeds.insert(points, entry),
+            _ => self.canvas.seeds.push(entry),
+        }
+        return self;
+    }
+
+    pub fn blocked_region(mut self, region: Region) -> PaintingBuilder {
+        self.canvas.blocked_regions.push(region);
+        return self;
+    }
+
+    pub fn color_source(mut self, colors: ColorConfig) -> PaintingBuilder {
+        self.colors = colors;
+        self.custom_color_source = None;
+        return self;
+    }
+
+    // paint with colors from a source of your own instead of a configured one, seed entries
+    // without colors of their own draw from it too
+    pub fn custom_color_source(mut self, source: Box<dyn ColorSource>) -> PaintingBuilder {
+        self.custom_color_source = Some(source);
+        return self;
+    }
+
+    // every region grows only from the colors of the seed entry it started from
+    pub fn region_colors(mut self, region_colors: bool) -> PaintingBuilder {
+        self.region_colors = region_colors;
+        return self;
+    }
+
+    pub fn metric(mut self, metric: PlacementMetric) -> PaintingBuilder {
+        self.placement.metric = metric;
+        return self;
+    }
+
+    pub fn distance(mut self, distance: DistanceMetric) -> PaintingBuilder {
+        self.placement.distance = distance;
+        return self;
+    }
+
+    pub fn selector(mut self, selector: SelectorKind) -> PaintingBuilder {
+        self.placement.selector = selector;
+        self.custom_selector = None;
+        return self;
+    }
+
+    // find locations with a selector of your own instead of a built-in one, it is told about
+    // every change of the boundry region from the starting locations on
+    pub fn custom_selector(mut self, selector: Box<dyn PositionSelector>) -> PaintingBuilder {
+        self.custom_selector = Some(selector);
+        return self;
+    }
+
+    // the neighbors used both to grow the boundry region and to score its locations
+    pub fn neighborhood(mut self, neighborhood: Neighborhood) -> PaintingBuilder {
+        self.placement.scoring_neighborhood = neighborhood.clone();
+        self.placement.neighborhood = neighborhood;
+        return self;
+    }
+
+    // score boundry region locations against other neighbors than the ones they grew from,
+    // set after neighborhood
+    pub fn scoring_neighborhood(mut self, neighborhood: Neighborhood) -> PaintingBuilder {
+        self.placement.scoring_neighborhood = neighborhood;
+        return self;
+    }
+
+    // seed of every random choice, a random one is picked when none is set
+    pub fn rng_seed(mut self, seed: u64) -> PaintingBuilder {
+        self.seed = Some(seed);
+        return self;
+    }
+
+    // keep every color at its full 16 bits per channel next to the 8-bit image
+    pub fn deep_color(mut self, deep_color: bool) -> PaintingBuilder {
+        self.deep_color = deep_color;
+        return self;
+    }
+
+    // check the settings and paint the starting locations
+    pub fn build(self) -> Result<Painter, ConfigError> {
+        let finite_colors = match &self.custom_color_source {
+            Some(source) => source.remaining().is_some(),
+            None => {
+                config::check_colors(&self.colors, "colors")?;
+                self.colors.is_finite()
+            }
+        };
+        config::check_painting(&self.canvas, finite_colors)?;
+        if self.custom_selector.is_none() {
+            config::check_selector(&self.placement)?;
+        }
+        return initialize_canvas(self);
+    }
+}
+
+impl Painter {
+    // place one more pixel, false once the boundry region or a finite palette is exhausted
+    pub fn step(&mut self) -> bool {
+        return paint_next_pixel(self);
+    }
+
+    // place pixels until pixel_count pixels are placed in total, false once nothing is left
+    // to place
+    pub fn run_until(&mut self, pixel_count: u64) -> bool {
+        while self.canvas_stats.current_pixels_placed_count < pixel_count {
+            if !paint_next_pixel(self) {
+                return false;
+            }
+        }
+        return true;
+    }
+
+    pub fn run_to_completion(&mut self) {
+        while paint_next_pixel(self) {}
+    }
+
+    pub fn image(&self) -> &RgbImage {
+        return &self.image;
+    }
+
+    // the painting at 16 bits per channel, None unless the builder asked for deep color
+    pub fn deep_image(&self) -> Option<&Rgb16Image> {
+        return self.deep_image.as_ref();
+    }
+
+    // the empty locations next to painted ones, in no particular order
+    pub fn frontier(&self) -> &[Coordinate] {
+        return &self.boundry_region_list;
+    }
+
+    pub fn stats(&self) -> &Stats {
+        return &self.canvas_stats;
+    }
+
+    // the seed every random choice was drawn from
+    pub fn seed(&self) -> u64 {
+        return self.seed;
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        return (
+            self.canvas_constraints.x_size,
+            self.canvas_constraints.y_size,
+        );
+    }
+}
+
+// place one more pixel, false once the boundry region or a finite palette is exhausted
+fn paint_next_pixel(working_canvas: &mut Painter) -> bool {
+    if working_canvas.boundry_region_list.is_empty() {
+        return false;
+    }
+
+    // choose a color (and the region it grows when regions keep their own colors), stop once
+    // a finite palette runs out
+    let (deep_color, region): (Rgb<u16>, Option<u32>) = if working_canvas.region_colors {
+        match next_region_color(working_canvas) {
+            Some((color, region)) => (color, Some(region)),
+            None => return false,
+        }
+    } else {
+        match next_color(None, working_canvas) {
+            Some(color) => (color, None),
+            None => return false,
+        }
+    };
+
+    // determine best location
+    let target_color = rgb16_to_rgb8(deep_color);
+    let tie_salt: u64 = working_canvas.rng.gen::<u64>();
+    let target_pixel = get_best_position_for_color(target_color, region, tie_salt, working_canvas);
+
+    // update the canvas
+    place_pixel(&target_pixel, working_canvas);
+    place_deep_color(target_pixel.position, deep_color, working_canvas);
+    return true;
+}
+
+// pick a region with probability proportional to its boundry region size and draw a color from
+// its seed entry's colors, regions whose colors run out stop growing
+fn next_region_color(working_canvas: &mut Painter) -> Option<(Rgb<u16>, u32)> {
+    loop {
+        let total: usize = working_canvas
+            .region_frontier_counts
+            .iter()
+            .zip(working_canvas.region_exhausted.iter())
+            .filter(|(_, exhausted)| !**exhausted)
+            .map(|(count, _)| *count)
+            .sum();
+        if total == 0 {
+            return None;
+        }
+
+        // walk the regions until the drawn offset falls inside one
+        let mut choice = working_canvas.rng.gen_range(0..total);
+        let mut region = 0;
+        for (index, count) in working_canvas.region_frontier_counts.iter().enumerate() {
+            if working_canvas.region_exhausted[index] {
+                continue;
+            }
+            if choice < *count {
+                region = index;
+                break;
+            }
+            choice -= count;
+        }
+
+        let entry = working_canvas.starting_locations[region].entry;
+        match next_color(Some(entry), working_canvas) {
+            Some(color) => return Some((color, region as u32)),
+            None => working_canvas.region_exhausted[region] = true,
+        }
+    }
+}
+
+// draw a color from the seed entry's own colors, or from the shared colors when there is no
+// entry or it has none, None once that source runs out
+fn next_color(entry: Option<usize>, working_canvas: &mut Painter) -> Option<Rgb<u16>> {
+    let shared: &mut dyn ColorSource = working_canvas.color_source.as_mut();
+    let source = match entry {
+        Some(entry) => working_canvas.seed_color_sources[entry].source(shared),
+        None => shared,
+    };
+    return source.next_color(&mut working_canvas.rng);
+}
+
+fn initialize_canvas(mut builder: PaintingBuilder) -> Result<Painter, ConfigError> {
+    // hold the random number generator, every random choice is drawn from it so that a
+    // fixed seed reproduces the same painting
+    let seed: u64 = builder.seed.unwrap_or_else(random::<u64>);
+    let mut working_rng: PaintRng = PaintRng::from_seed(seed);
+
+    // hold the shared color source
+    let working_color_source: Box<dyn ColorSource> = match builder.custom_color_source.take() {
+        Some(source) => source,
+        None => colors::color_source(&builder.colors, &mut working_rng),
+    };
+
+    // hold the output image dimensions, "auto" sizes the canvas to fit a finite palette
+    let working_constraints: Constraints = match builder.canvas.size {
+        CanvasSize::Auto => fit_canvas_to_palette(working_color_source.remaining().unwrap_or(0)),
+        CanvasSize::Fixed { x_size, y_size } => Constraints { x_size, y_size },
+    };
+
+    // hold the colors of every seed entry
+    let seed_color_sources: Vec<SeedColorSource> = builder
+        .canvas
+        .seeds
+        .iter()
+        .map(|entry| SeedColorSource::new(&entry.colors, &mut working_rng))
+        .collect();
+
+    // an automatic size is only known now, every location must be indexable
+    config::check_canvas_size(working_constraints.x_size, working_constraints.y_size)?;
+
+    // every explicit starting location must be on the canvas, seed patterns are clipped to it
+    config::check_starting_locations(
+        &builder.canvas.seeds,
+        working_constraints.x_size,
+        working_constraints.y_size,
+    )?;
+    let starting_points: Vec<Seed> = seeds::resolve_starting_locations(
+        &builder.canvas.seeds,
+        working_constraints.x_size,
+        working_constraints.y_size,
+        &mut working_rng,
+    );
+
+    // hold running stats
+    let working_stats: Stats = Stats {
+        current_pixels_placed_count: 0u64,
+        start_time: Instant::now(),
+    };
+
+    // hold the strategy that finds the best location for a color
+    let pixel_count = working_constraints.x_size as usize * working_constraints.y_size as usize;
+    let working_position_selector: Box<dyn PositionSelector> = match builder.custom_selector.take()
+    {
+        Some(selector) => selector,
+        None => selectors::position_selector(
+            builder.placement.selector,
+            builder.placement.distance,
+            pixel_count,
+        ),
+    };
+
+    // hold each placed pixel's color converted for the distance metric
+    let working_color_cache: Vec<[f32; 3]> = vec![[0f32; 3]; pixel_count];
+
+    // hold the state of every location, blocked regions are never painted
+    let mut working_occupancy: Vec<CellState> = vec![CellState::Empty; pixel_count];
+    for region in builder.canvas.blocked_regions.iter() {
+        let y_end = region.y.saturating_add(region.height);
+        let x_end = region.x.saturating_add(region.width);
+        for region_y in region.y..y_end.min(working_constraints.y_size) {
+            for region_x in region.x..x_end.min(working_constraints.x_size) {
+                working_occupancy[pixel_index(region_x, region_y, &working_constraints)] =
+                    CellState::Blocked;
+            }
+        }
+    }
+
+    // hold the region every location grew from
+    let region_count = starting_points.len();
+    let working_owners: Vec<u32> = vec![NO_OWNER; pixel_count];
+
+    // hold all info required for painting
+    let mut working_canvas: Painter = Painter {
+        image: RgbImage::new(working_constraints.x_size, working_constraints.y_size),
+        deep_image: match builder.deep_color {
+            true => Some(Rgb16Image::new(
+                working_constraints.x_size,
+                working_constraints.y_size,
+            )),
+            false => None,
+        },
+        seed,
+        occupancy: working_occupancy,
+        boundry_region_positions: vec![0; pixel_count],
+        starting_locations: starting_points,
+        seed_color_sources,
+        canvas_constraints: working_constraints,
+        canvas_stats: working_stats,
+        color_source: working_color_source,
+        placement_metric: builder.placement.metric,
+        distance_metric: builder.placement.distance,
+        growth_neighborhood: builder.placement.neighborhood.clone(),
+        scoring_neighborhood: builder.placement.scoring_neighborhood.clone(),
+        color_cache: working_color_cache,
+        position_selector: working_position_selector,
+        rng: working_rng,
+        boundry_region_list: Vec::new(),
+        owners: working_owners,
+        region_frontier_counts: vec![0; region_count],
+        region_colors: builder.region_colors,
+        region_exhausted: vec![false; region_count],
+        placement_order: vec![NOT_PLACED; pixel_count],
+        placement_times: vec![f32::NAN; pixel_count],
+    };
+
+    // loop over starting positions and place each seed entry's colors at them
+    for index in 0..working_canvas.starting_locations.len() {
+        // skip starting positions that are blocked or already painted
+        let seed = working_canvas.starting_locations[index];
+        let location_index = pixel_index(
+            seed.position.x,
+            seed.position.y,
+            &working_canvas.canvas_constraints,
+        );
+        if matches!(
+            working_canvas.occupancy[location_index],
+            CellState::Filled | CellState::Blocked
+        ) {
+            continue;
+        }
+
+        // choose a color, skip the seed once its finite colors run out
+        let fixed_color = working_canvas.seed_color_sources[seed.entry].fixed_color();
+        let deep_color: Rgb<u16> =
+            match fixed_color.or_else(|| next_color(Some(seed.entry), &mut working_canvas)) {
+                Some(color) => color,
+                None => continue,
+            };
+
+        // the seed starts its own region, even where another region already reached
+        claim_location(location_index, index as u32, &mut working_canvas);
+        let target_pixel = Pixel {
+            position: seed.position,
+            color: rgb16_to_rgb8(deep_color),
+        };
+        place_pixel(&target_pixel, &mut working_canvas);
+        place_deep_color(seed.position, deep_color, &mut working_canvas);
+    }
+
+    return Ok(working_canvas);
+}
+
+// smallest roughly square canvas with at least one pixel per palette color
+fn fit_canvas_to_palette(color_count: usize) -> Constraints {
+    let x_size = (color_count as f64).sqrt().ceil().max(1f64) as u32;
+    let y_size = ((color_count as f64 / x_size as f64).ceil() as u32).max(1);
+    return Constraints { x_size, y_size };
+}
+
+// hand a location to a region, keeping the boundry region sizes of both regions up to date
+fn claim_location(cell_index: usize, owner: u32, working_canvas: &mut Painter) {
+    let previous_owner = working_canvas.owners[cell_index];
+    if working_canvas.occupancy[cell_index] == CellState::Frontier {
+        if previous_owner != NO_OWNER {
+            working_canvas.region_frontier_counts[previous_owner as usize] -= 1;
+        }
+        working_canvas.region_frontier_counts[owner as usize] += 1;
+    }
+    working_canvas.owners[cell_index] = owner;
+}
+
+// keep the full precision of a placed color when the painting is written with 16 bits
+fn place_deep_color(position: Coordinate, color: Rgb<u16>, working_canvas: &mut Painter) {
+    if let Some(deep_image) = &mut working_canvas.deep_image {
+        deep_image.put_pixel(position.x, position.y, color);
+    }
+}
+
+// index of a pixel in the per-pixel canvas buffers
+fn pixel_index(x: u32, y: u32, canvas_constraints: &Constraints) -> usize {
+    return x as usize + y as usize * canvas_constraints.x_size as usize;
+}
+
+// update a pixel on the canvas and add its neighbors to the boundry region
+fn place_pixel(target: &Pixel, working_canvas: &mut Painter) {
+    let target_index = pixel_index(
+        target.position.x,
+        target.position.y,
+        &working_canvas.canvas_constraints,
+    );
+
+    // remove the target from the boundry region LIST, swap remove is much faster
+    if working_canvas.occupancy[target_index] == CellState::Frontier {
+        let list_index = working_canvas.boundry_region_positions[target_index];
+        working_canvas.boundry_region_list.swap_remove(list_index);
+        if let Some(moved) = working_canvas.boundry_region_list.get(list_index) {
+            let moved_index = pixel_index(moved.x, moved.y, &working_canvas.canvas_constraints);
+            working_canvas.boundry_region_positions[moved_index] = list_index;
+        }
+        working_canvas
+            .position_selector
+            .frontier_removed(target_index);
+        let owner = working_canvas.owners[target_index];
+        if owner != NO_OWNER {
+            working_canvas.region_frontier_counts[owner as usize] -= 1;
+        }
+    }
+
+    // update a pixel on the canvas
+    working_canvas
+        .image
+        .put_pixel(target.position.x, target.position.y, target.color);
+    working_canvas.occupancy[target_index] = CellState::Filled;
+
+    // cache the color converted for the distance metric
+    working_canvas.color_cache[target_index] = working_canvas.distance_metric.convert(target.color);
+
+    // record when the pixel was placed and update counter
+    working_canvas.placement_order[target_index] =
+        working_canvas.canvas_stats.current_pixels_placed_count as u32;
+    working_canvas.placement_times[target_index] = working_canvas
+        .canvas_stats
+        .start_time
+        .elapsed()
+        .as_secs_f32();
+    working_canvas.canvas_stats.current_pixels_placed_count += 1;
+
+    let x_size = working_canvas.canvas_constraints.x_size;
+    let y_size = working_canvas.canvas_constraints.y_size;
+
+    // the target is a new painted neighbor of every boundry region location scored against it
+    for (location, _) in
+        working_canvas
+            .scoring_neighborhood
+            .reaching(target.position, x_size, y_size)
+    {
+        let cell = pixel_index(location.x, location.y, &working_canvas.canvas_constraints);
+        if working_canvas.occupancy[cell] == CellState::Frontier {
+            working_canvas
+                .position_selector
+                .neighbor_painted(cell, working_canvas.color_cache[target_index]);
+        }
+    }
+
+    // loop over the neighbors the target grows into
+    for (location, _) in working_canvas
+        .growth_neighborhood
+        .around(target.position, x_size, y_size)
+    {
+        // only empty locations join the boundry region, this prevents duplicates
+        let neighbor_index =
+            pixel_index(location.x, location.y, &working_canvas.canvas_constraints);
+        if working_canvas.occupancy[neighbor_index] != CellState::Empty {
+            continue;
+        }
+
+        // add this neighbor to the boundry region LIST
+        working_canvas.boundry_region_positions[neighbor_index] =
+            working_canvas.boundry_region_list.len();
+        working_canvas.boundry_region_list.push(location);
+        working_canvas.occupancy[neighbor_index] = CellState::Frontier;
+        working_canvas
+            .position_selector
+            .frontier_added(neighbor_index);
+
+        // report every painted location the new boundry region location is scored against,
+        // including the target unless the neighborhoods differ
+        for (scored, _) in working_canvas
+            .scoring_neighborhood
+            .around(location, x_size, y_size)
+        {
+            let cell = pixel_index(scored.x, scored.y, &working_canvas.canvas_constraints);
+            if working_canvas.occupancy[cell] == CellState::Filled {
+                working_canvas
+                    .position_selector
+                    .neighbor_painted(neighbor_index, working_canvas.color_cache[cell]);
+            }
+        }
+
+        // the neighbor joins the region of the pixel that brought it into the boundry region
+        let owner = working_canvas.owners[target_index];
+        working_canvas.owners[neighbor_index] = owner;
+        if owner != NO_OWNER {
+            working_canvas.region_frontier_counts[owner as usize] += 1;
+        }
+    }
+}
+
+// find the boundry region location best suited for a color, only considering locations of the
+// given region if there is one, equally scored locations are ranked by tie_break_key so the
+// result does not depend on evaluation order
+fn get_best_position_for_color(
+    target_color: Rgb<u8>,
+    region: Option<u32>,
+    tie_salt: u64,
+    working_canvas: &Painter,
+) -> Pixel {
+    // convert the target color once for all positions
+    let converted_target_color: [f32; 3] = working_canvas.distance_metric.convert(target_color);
+
+    let candidates = Candidates {
+        painter: working_canvas,
+        region,
+        tie_salt,
+    };
+    // a selector may not know locations without painted scoring neighbors, when those are all
+    // that is left every location is scored
+    let best_index = working_canvas
+        .position_selector
+        .select(&converted_target_color, &candidates)
+        .or_else(|| BruteForceSelector.select(&converted_target_color, &candidates))
+        .expect("[ERROR] no boundry region location left for the color");
+    let x_size = working_canvas.canvas_constraints.x_size as usize;
+    return Pixel {
+        color: target_color,
+        position: Coordinate {
+            x: (best_index % x_size) as u32,
+            y: (best_index / x_size) as u32,
+        },
+    };
+}
+
+fn evaluate_position(
+    target_location: &Coordinate,
+    target_color: &[f32; 3],
+    working_canvas: &Painter,
+) -> (f32, Coordinate) {
+    let canvas_constraints: &Constraints = &working_canvas.canvas_constraints;
+    let neighborhood: &Neighborhood = &working_canvas.scoring_neighborhood;
+
+    // small neighborhoods keep their distances and weights on the stack
+    let mut stack_buffer: [f32; 2 * STACK_NEIGHBORS] = [0f32; 2 * STACK_NEIGHBORS];
+    let mut heap_buffer: Vec<f32> = Vec::new();
+    let buffer: &mut [f32] = if neighborhood.len() <= STACK_NEIGHBORS {
+        &mut stack_buffer
+    } else {
+        heap_buffer.resize(2 * neighborhood.len(), 0f32);
+        &mut heap_buffer
+    };
+    let (neighbor_distances, neighbor_weights) = buffer.split_at_mut(buffer.len() / 2);
+    let mut neighbor_count: usize = 0;
+
+    // loop over the neighbors the location is scored against
+    for (neighbor, weight) in neighborhood.around(
+        *target_location,
+        canvas_constraints.x_size,
+        canvas_constraints.y_size,
+    ) {
+        let neighbor_index = pixel_index(neighbor.x, neighbor.y, canvas_constraints);
+
+        // skip un-colored
+        if working_canvas.occupancy[neighbor_index] != CellState::Filled {
+            continue;
+        }
+
+        // compute color distance using the cached conversion of the neighbor's color
+        neighbor_distances[neighbor_count] = working_canvas
+            .distance_metric
+            .distance(target_color, &working_canvas.color_cache[neighbor_index]);
+        neighbor_weights[neighbor_count] = weight;
+        neighbor_count += 1;
+    }
+
+    // combine neighbor distances using the configured metric
+    let score: f32 = working_canvas.placement_metric.score(
+        &mut neighbor_distances[..neighbor_count],
+        &neighbor_weights[..neighbor_count],
+    );
+    return (score, *target_location);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use colors::{Palette, PaletteOptions};
+    use selectors::IndexedSelector;
+    use std::path::PathBuf;
+    use std::time::SystemTime;
+    use strict_yaml_rust::StrictYamlLoader;
+
+    // a file name in the temporary directory only this test process uses
+    pub(crate) fn temp_path(name: &str) -> PathBuf {
+        return std::env::temp_dir().join(format!(
+            "rusty-color-shredder-{}-{}",
+            std::process::id(),
+            name
+        ));
+    }
+
+    // an empty canvas that can be painted by hand
+    fn test_canvas(x_size: u32, y_size: u32, placement_metric: PlacementMetric) -> Painter {
+        return Painter {
+            image: RgbImage::new(x_size, y_size),
+            deep_image: None,
+            seed: 0,
+            occupancy: vec![CellState::Empty; (x_size * y_size) as usize],
+            boundry_region_list: Vec::new(),
+            boundry_region_positions: vec![0; (x_size * y_size) as usize],
+            starting_locations: Vec::new(),
+            seed_color_sources: Vec::new(),
+            canvas_constraints: Constraints { x_size, y_size },
+            canvas_stats: Stats {
+                current_pixels_placed_count: 0,
+                start_time: Instant::now(),
+            },
+            color_source: Box::new(Palette {
+                colors: Vec::new(),
+                index: 0,
+            }),
+            placement_metric,
+            distance_metric: DistanceMetric::Rgb,
+            growth_neighborhood: Neighborhood::moore(),
+            scoring_neighborhood: Neighborhood::moore(),
+            color_cache: vec![[0f32; 3]; (x_size * y_size) as usize],
+            position_selector: Box::new(BruteForceSelector),
+            rng: PaintRng::from_seed(0),
+            owners: vec![NO_OWNER; (x_size * y_size) as usize],
+            region_frontier_counts: Vec::new(),
+            region_colors: false,
+            region_exhausted: Vec::new(),
+            placement_order: vec![NOT_PLACED; (x_size * y_size) as usize],
+            placement_times: vec![f32::NAN; (x_size * y_size) as usize],
+        };
+    }
+
+    // a 7x1 strip: red at 0, blue at 2 and dark red at 5, leaving positions 1, 3, 4 and 6
+    // available, position 1 has an exact red neighbor but also a blue one
+    fn painted_strip(placement_metric: PlacementMetric) -> Painter {
+        let mut working_canvas = test_canvas(7, 1, placement_metric);
+        for (x, color) in [(0, [255, 0, 0]), (2, [0, 0, 255]), (5, [200, 0, 0])] {
+            let target_pixel = Pixel {
+                position: Coordinate { x, y: 0 },
+                color: Rgb(color),
+            };
+            place_pixel(&target_pixel, &mut working_canvas);
+        }
+        return working_canvas;
+    }
+
+    #[test]
+    fn min_metric_places_next_to_closest_neighbor() {
+        let working_canvas = painted_strip(PlacementMetric::Min);
+        let target_pixel = get_best_position_for_color(Rgb([255, 0, 0]), None, 0, &working_canvas);
+        assert_eq!(target_pixel.position.x, 1);
+    }
+
+    #[test]
+    fn average_metric_places_next_to_closest_mean() {
+        let working_canvas = painted_strip(PlacementMetric::Average);
+        let target_pixel = get_best_position_for_color(Rgb([255, 0, 0]), None, 0, &working_canvas);
+        assert!(target_pixel.position.x == 4 || target_pixel.position.x == 6);
+    }
+
+    // paint a small canvas to completion from an inline config
+    fn palette_config(seed: &str, selector: &str) -> Config {
+        let config_string = format!(
+            "config:
+  seed: \"{seed}\"
+  canvas:
+    size:
+      x: \"32\"
+      y: \"24\"
+    starting_locations:
+      - x: 0
+        y: 0
+      - x: 20
+        y: 10
+  colors:
+    generation_mode: \"palette\"
+    palette:
+      bit_depth: \"4\"
+    generation_color_space: \"hsv\"
+  placement:
+    selector: \"{selector}\"
+"
+        );
+        let config = StrictYamlLoader::load_from_str(&config_string).unwrap()[0]["config"].clone();
+        return Config::from_yaml(&config).unwrap();
+    }
+
+    fn render(seed: &str, selector: &str, threads: usize) -> Vec<u8> {
+        let config = palette_config(seed, selector);
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        return pool.install(|| {
+            let mut working_canvas = PaintingBuilder::from_config(&config).build().unwrap();
+            working_canvas.run_to_completion();
+            working_canvas.image.into_raw()
+        });
+    }
+
+    #[test]
+    fn regions_grow_from_their_own_colors() {
+        // a red seed on the left and a blue one on the right, each with its own palette
+        let seed_entry = |x: u32, channel: &str| {
+            format!(
+                "      - x: {x}
+        y: 4
+        colors:
+          generation_mode: \"palette\"
+          palette:
+            bit_depth: \"4\"
+          generation_color_space: \"rgb\"
+          color_channel_options:
+            {channel}:
+              lock: \"true\"
+              value: 0
+"
+            )
+        };
+        let config_string = format!(
+            "config:
+  seed: \"1\"
+  canvas:
+    size:
+      x: \"16\"
+      y: \"8\"
+    starting_locations:
+{}{}  colors:
+    per_region: \"true\"
+    generation_mode: \"random\"
+    generation_color_space: \"rgb\"
+",
+            seed_entry(0, "channel_3"),
+            seed_entry(15, "channel_1")
+        );
+        let config = StrictYamlLoader::load_from_str(&config_string).unwrap()[0]["config"].clone();
+        let mut working_canvas = PaintingBuilder::from_config(&Config::from_yaml(&config).unwrap())
+            .build()
+            .unwrap();
+        working_canvas.run_to_completion();
+
+        // either palette alone could fill the canvas
+        assert_eq!(working_canvas.canvas_stats.current_pixels_placed_count, 128);
+        for (index, pixel) in working_canvas.image.pixels().enumerate() {
+            match working_canvas.owners[index] {
+                0 => assert_eq!(pixel[2], 0),
+                1 => assert_eq!(pixel[0], 0),
+                owner => panic!("unexpected owner {}", owner),
+            }
+        }
+        assert!(working_canvas
+            .region_frontier_counts
+            .iter()
+            .all(|count| *count == 0));
+    }
+
+    #[test]
+    fn seeded_runs_are_reproducible() {
+        let golden = render("42", "brute_force", 1);
+        assert_eq!(render("42", "brute_force", 4), golden);
+        assert_eq!(render("42", "indexed", 1), golden);
+        assert_ne!(render("43", "brute_force", 4), golden);
+    }
+
+    #[test]
+    fn resumed_checkpoint_matches_an_uninterrupted_run() {
+        for selector in ["brute_force", "indexed"] {
+            let config = palette_config("7", selector);
+            let builder = || PaintingBuilder::from_config(&config);
+            let mut uninterrupted = builder().build().unwrap();
+            uninterrupted.run_to_completion();
+
+            let mut interrupted = builder().build().unwrap();
+            interrupted.run_until(300);
+            let bytes = checkpoint::encode_checkpoint(&interrupted, "", SystemTime::now());
+            let mut resumed = builder().build().unwrap();
+            checkpoint::decode_checkpoint(&bytes)
+                .unwrap()
+                .restore(&mut resumed)
+                .unwrap();
+            resumed.run_to_completion();
+
+            assert!(resumed.image == uninterrupted.image);
+            assert_eq!(resumed.placement_order, uninterrupted.placement_order);
+            assert_eq!(resumed.owners, uninterrupted.owners);
+        }
+    }
+
+    #[test]
+    fn separate_neighborhoods_fill_the_canvas() {
+        let render = |selector: &str| {
+            let config = palette_config("11", selector);
+            let mut working_canvas = PaintingBuilder::from_config(&config)
+                .neighborhood(Neighborhood::von_neumann())
+                .scoring_neighborhood(Neighborhood::disc(2))
+                .build()
+                .unwrap();
+            working_canvas.run_to_completion();
+            assert_eq!(working_canvas.stats().current_pixels_placed_count, 32 * 24);
+            working_canvas.image.into_raw()
+        };
+        // both selectors see the same scoring neighbors
+        assert_eq!(render("indexed"), render("brute_force"));
+    }
+
+    #[test]
+    fn indexed_selector_paints_one_sided_neighborhoods() {
+        let make_builders: [fn() -> PaintingBuilder; 2] = [
+            // a kernel that only reaches to the right, locations grown into never see their pixel
+            || {
+                let one_sided = Neighborhood::kernel(&[
+                    vec![0f32, 0f32, 0f32],
+                    vec![0f32, 0f32, 1f32],
+                    vec![0f32, 0f32, 0f32],
+                ])
+                .unwrap();
+                PaintingBuilder::new(6, 1)
+                    .neighborhood(one_sided)
+                    .starting_location(0, 0)
+                    .rng_seed(3)
+            },
+            // growing over the blocked location leaves a location without painted moore neighbors
+            || {
+                PaintingBuilder::new(5, 1)
+                    .neighborhood(Neighborhood::disc(2))
+                    .scoring_neighborhood(Neighborhood::moore())
+                    .blocked_region(Region {
+                        x: 1,
+                        y: 0,
+                        width: 1,
+                        height: 1,
+                    })
+                    .starting_location(0, 0)
+                    .rng_seed(3)
+            },
+        ];
+
+        for make_builder in make_builders {
+            assert!(make_builder()
+                .selector(SelectorKind::Indexed)
+                .build()
+                .is_err());
+
+            let mut brute_force = make_builder().build().unwrap();
+            brute_force.run_to_completion();
+            let cell_count = brute_force.occupancy.len();
+            let mut indexed = make_builder()
+                .custom_selector(Box::new(IndexedSelector::new(
+                    DistanceMetric::Rgb,
+                    cell_count,
+                )))
+                .build()
+                .unwrap();
+            indexed.run_to_completion();
+            assert!(indexed.image == brute_force.image);
+        }
+    }
+
+    #[test]
+    fn builder_paints_without_a_config() {
+        let mut painter = PaintingBuilder::new(12, 8)
+            .starting_location(3, 4)
+            .starting_location(10, 1)
+            .rng_seed(5)
+            .deep_color(true)
+            .build()
+            .unwrap();
+        assert_eq!(painter.stats().current_pixels_placed_count, 2);
+        assert!(painter.run_until(40));
+        assert_eq!(painter.stats().current_pixels_placed_count, 40);
+        assert!(!painter.frontier().is_empty());
+
+        painter.run_to_completion();
+        assert_eq!(painter.stats().current_pixels_placed_count, 96);
+        assert!(painter.frontier().is_empty());
+        assert!(!painter.run_until(100));
+        for (pixel, deep_pixel) in painter
+            .image()
+            .pixels()
+            .zip(painter.deep_image().unwrap().pixels())
+        {
+            assert_eq!(*pixel, rgb16_to_rgb8(*deep_pixel));
+        }
+
+        // the same settings paint the same picture
+        let mut repeated = PaintingBuilder::new(12, 8)
+            .starting_location(3, 4)
+            .starting_location(10, 1)
+            .rng_seed(5)
+            .build()
+            .unwrap();
+        repeated.run_to_completion();
+        assert!(repeated.image() == painter.image());
+        assert!(PaintingBuilder::new(12, 8)
+            .starting_location(12, 0)
+            .build()
+            .is_err());
+    }
+
+    // every gray from black up to a limit, once
+    struct Grays {
+        next: u16,
+        limit: u16,
+    }
+
+    impl ColorSource for Grays {
+        fn next_color(&mut self, _rng: &mut PaintRng) -> Option<Rgb<u16>> {
+            if self.next >= self.limit {
+                return None;
+            }
+            let value = self.next * 257;
+            self.next += 1;
+            return Some(Rgb([value, value, value]));
+        }
+
+        fn remaining(&self) -> Option<usize> {
+            return Some((self.limit - self.next) as usize);
+        }
+    }
+
+    #[test]
+    fn builder_paints_with_a_custom_color_source() {
+        let mut painter = PaintingBuilder::new(1, 1)
+            .fit_to_palette()
+            .custom_color_source(Box::new(Grays {
+                next: 0,
+                limit: 196,
+            }))
+            .starting_location(0, 0)
+            .build()
+            .unwrap();
+        painter.run_to_completion();
+
+        // the canvas fits the 196 grays, each painted once
+        assert_eq!(painter.size(), (14, 14));
+        let mut grays: Vec<u8> = painter.image().pixels().map(|pixel| pixel[0]).collect();
+        grays.sort_unstable();
+        assert_eq!(grays, (0..196).collect::<Vec<u8>>());
+        assert!(painter.image().pixels().all(|pixel| pixel[0] == pixel[1]));
+    }
+
+    #[test]
+    fn builder_rejects_settings_that_cannot_paint() {
+        let pattern_error = |pattern: SeedPattern| {
+            return PaintingBuilder::new(8, 8)
+                .seed_entry(SeedEntry {
+                    pattern,
+                    colors: SeedColors::default(),
+                })
+                .build()
+                .err();
+        };
+        assert_eq!(
+            pattern_error(SeedPattern::Edges { spacing: 0 }),
+            Some(ConfigError::ZeroSize {
+                key: String::from("canvas.seeds.0.spacing"),
+            })
+        );
+        assert!(pattern_error(SeedPattern::Grid {
+            spacing_x: 0,
+            spacing_y: 2,
+            offset_x: 0,
+            offset_y: 0,
+        })
+        .is_some());
+        assert!(pattern_error(SeedPattern::PoissonDisk {
+            min_distance: 0f32,
+            attempts: 30,
+        })
+        .is_some());
+
+        let palette_error = |bit_depth: u32, group_by_channel: usize| {
+            let channel = ChannelOptions {
+                lock: None,
+                min: 0f32,
+                max: 1f32,
+            };
+            return PaintingBuilder::new(8, 8)
+                .starting_location(0, 0)
+                .color_source(ColorConfig::Palette(PaletteOptions {
+                    color_options: ColorOptions {
+                        color_space: ColorSpace::Rgb,
+                        channels: [channel; 3],
+                    },
+                    bit_depth,
+                    group_by_channel,
+                    shuffle: true,
+                }))
+                .build()
+                .err();
+        };
+        assert!(palette_error(2, 1).is_none());
+        assert_eq!(
+            palette_error(32, 1),
+            Some(ConfigError::InvalidValue {
+                key: String::from("colors.palette.bit_depth"),
+                value: String::from("32"),
+                expected: String::from("a value between 1 and 8"),
+            })
+        );
+        assert!(palette_error(2, 0).is_some());
+    }
+
+    #[test]
+    fn palettes_fit_the_smallest_near_square_canvas() {
+        for (color_count, size) in [
+            (0, (1, 1)),
+            (1, (1, 1)),
+            (64, (8, 8)),
+            (65, (9, 8)),
+            (72, (9, 8)),
+            (73, (9, 9)),
+            (4096, (64, 64)),
+        ] {
+            let constraints = fit_canvas_to_palette(color_count);
+            assert_eq!((constraints.x_size, constraints.y_size), size);
+        }
+    }
+
+    #[test]
+    fn black_is_a_painted_color() {
+        let mut working_canvas = test_canvas(3, 1, PlacementMetric::Min);
+        for x in [0, 1] {
+            let target_pixel = Pixel {
+                position: Coordinate { x, y: 0 },
+                color: Rgb([0, 0, 0]),
+            };
+            place_pixel(&target_pixel, &mut working_canvas);
+        }
+        assert_eq!(working_canvas.boundry_region_list.len(), 1);
+        assert_eq!(working_canvas.boundry_region_list[0].x, 2);
+        assert!(working_canvas.occupancy[..2]
+            .iter()
+            .all(|state| *state == CellState::Filled));
+    }
+}
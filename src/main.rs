@@ -1,413 +1,259 @@
-use image::{GrayImage, ImageFormat, Luma, Rgb, RgbImage};
-use palette::{convert::TryIntoColor, Hsv, Srgb};
-use rand::random;
-use rayon::prelude::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
-use std::{fs, time::Instant};
-use strict_yaml_rust::StrictYamlLoader;
-
-struct Painting {
-    image: RgbImage,
-    boundry_region_image: GrayImage,
-    boundry_region_list: Vec<Coordinate>,
-    starting_locations: Vec<Coordinate>,
-    canvas_constraints: Constraints,
-    canvas_stats: Stats,
-}
-struct Constraints {
-    x_size: u32,
-    y_size: u32,
-}
-struct Stats {
-    current_pixels_placed_count: u64,
-}
-struct Pixel {
-    position: Coordinate,
-    color: Rgb<u8>,
-}
-#[derive(Copy, Clone)]
-struct Coordinate {
-    x: u32,
-    y: u32,
-}
+#![allow(clippy::needless_return)]
+
+mod cli;
+
+use cli::{Arguments, Command};
+use rusty_color_shredder::animation::AnimationRecorder;
+use rusty_color_shredder::checkpoint::{self, Checkpoint};
+use rusty_color_shredder::config::{self, Config, ConfigError};
+use rusty_color_shredder::{metadata, output, Painter, PaintingBuilder};
+use std::{
+    env, fs,
+    path::Path,
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Instant, SystemTime},
+};
+use strict_yaml_rust::StrictYaml;
 
 fn main() {
-    // set output constraints, initialize canvas, get starting positions
-    let mut working_canvas: Painting = initialize_canvas();
-
-    // initial update of the output files
-    write_output_files(&working_canvas);
-
-    // create a timer to update at regular intervals
-    let mut current_time = Instant::now();
-
-    // run the simulation loop as long as there are available positions in the boundry region
-    while working_canvas.boundry_region_list.len() > 0 {
-        let temp_color: Srgb = Hsv::new(
-            0.55f32 * 360f32,
-            random::<f32>().clamp(0.5f32, 1.0f32),
-            random::<f32>().clamp(0.0f32, 1.0f32),
-        )
-        .try_into_color()
-        .unwrap();
-        let temp_color_alt: Srgb = Hsv::new(
-            0.59f32 * 360f32,
-            random::<f32>().clamp(0.5f32, 1.0f32),
-            random::<f32>().clamp(0.0f32, 1.0f32),
-        )
-        .try_into_color()
-        .unwrap();
-
-        // choose a random color
-        let mut target_color: Rgb<u8> = Rgb([
-            (temp_color.red * 255f32).floor() as u8,
-            (temp_color.green * 255f32).floor() as u8,
-            (temp_color.blue * 255f32).floor() as u8,
-        ]);
-        if random::<bool>() {
-            target_color = Rgb([
-                (temp_color_alt.red * 255f32).floor() as u8,
-                (temp_color_alt.green * 255f32).floor() as u8,
-                (temp_color_alt.blue * 255f32).floor() as u8,
-            ]);
+    // read command line arguments
+    let arguments = match cli::parse_arguments(env::args().skip(1)) {
+        Ok(arguments) => arguments,
+        Err(message) => {
+            eprintln!("[ERROR] {}\n\n{}", message, cli::USAGE);
+            process::exit(2);
         }
-        // determine best location
-        let target_pixel = get_best_position_for_color(target_color, &mut working_canvas);
-
-        // update the canvas
-        place_pixel(&target_pixel, &mut working_canvas);
-
-        // update output files after given interval
-        if current_time.elapsed().as_secs_f32() > (1f32 / 2f32) {
-            current_time = Instant::now();
-            write_output_files(&working_canvas);
-        }
-    }
-
-    // final update of the output files
-    write_output_files(&working_canvas);
-}
-
-fn initialize_canvas() -> Painting {
-    // read config file into a string
-    let config_string =
-        fs::read_to_string("./config/config.yaml").expect("[ERROR] unable to read config.yaml");
-
-    // parse config string using yaml structure
-    let config = &StrictYamlLoader::load_from_str(&config_string)
-        .expect("[ERROR] unable to parse config.yaml")[0]["config"];
-
-    // hold the output image dimensions
-    let working_constraints: Constraints = Constraints {
-        x_size: config["canvas"]["size"]["x"]
-            .as_str()
-            .expect("[ERROR] failed to parse config value as string")
-            .parse::<u32>()
-            .expect("[ERROR] failed to convert config str to int"),
-        y_size: config["canvas"]["size"]["y"]
-            .as_str()
-            .expect("[ERROR] failed to parse config value as string")
-            .parse::<u32>()
-            .expect("[ERROR] failed to convert config str to int"),
     };
+    if arguments.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
 
-    // hold running stats
-    let working_stats: Stats = Stats {
-        current_pixels_placed_count: 0u64,
-    };
-
-    let mut starting_points = Vec::new();
-
-    // CUSTOMIZED:
-    for location in config["canvas"]["starting_locations"] {
-        let _x = &location["x"]
-        .as_str()
-        .expect("[ERROR] failed to parse config value as string")
-        .parse::<u32>()
-        .expect("[ERROR] failed to convert config str to int");
-        
-        let _y = &location["y"]
-        .as_str()
-        .expect("[ERROR] failed to parse config value as string")
-        .parse::<u32>()
-        .expect("[ERROR] failed to convert config str to int");
-
-
-        starting_points.push(Coordinate {
-            x: _x,
-            y: _y,
-        });
+    // print the config stored in a painting, which reproduces it
+    if arguments.command == Command::ExtractConfig {
+        match fs::read(&arguments.input_path)
+            .map_err(|error| error.to_string())
+            .and_then(|bytes| metadata::extract_config(&bytes))
+        {
+            Ok(stored) => {
+                if let Some(software) = stored.software.filter(|name| *name != metadata::software())
+                {
+                    eprintln!(
+                        "{} was painted by {}, the config may not reproduce it with {}",
+                        arguments.input_path,
+                        software,
+                        metadata::software()
+                    );
+                }
+                print!("{}", stored.config_text);
+            }
+            Err(message) => {
+                eprintln!("[ERROR] {}: {}", arguments.input_path, message);
+                process::exit(1);
+            }
+        }
+        return;
     }
 
-    // hold all info required for painting
-    let mut working_canvas: Painting = Painting {
-        image: RgbImage::new(working_constraints.x_size, working_constraints.y_size),
-        boundry_region_image: GrayImage::new(
-            working_constraints.x_size,
-            working_constraints.y_size,
-        ),
-        starting_locations: starting_points,
-        canvas_constraints: working_constraints,
-        canvas_stats: working_stats,
-        boundry_region_list: Vec::new(),
+    // a checkpoint carries the config it was painted with
+    let resumed_checkpoint: Option<Checkpoint> = match arguments.command {
+        Command::Resume => match checkpoint::read_checkpoint(Path::new(&arguments.input_path)) {
+            Ok(checkpoint) => Some(checkpoint),
+            Err(error) => {
+                eprintln!("[ERROR] {}: {}", arguments.input_path, error);
+                process::exit(1);
+            }
+        },
+        _ => None,
     };
 
-    // loop over starting positions and place random colors at each
-    for index in 0..working_canvas.starting_locations.len() {
-        let temp_color: Srgb = Hsv::new(
-            0.55f32 * 360f32,
-            random::<f32>().clamp(0.5f32, 1.0f32),
-            random::<f32>().clamp(0.0f32, 1.0f32),
-        )
-        .try_into_color()
-        .unwrap();
-        let temp_color_alt: Srgb = Hsv::new(
-            0.59f32 * 360f32,
-            random::<f32>().clamp(0.5f32, 1.0f32),
-            random::<f32>().clamp(0.0f32, 1.0f32),
-        )
-        .try_into_color()
-        .unwrap();
-
-        // choose a random color
-        let mut target_color: Rgb<u8> = Rgb([
-            (temp_color.red * 255f32).floor() as u8,
-            (temp_color.green * 255f32).floor() as u8,
-            (temp_color.blue * 255f32).floor() as u8,
-        ]);
-        if random::<bool>() {
-            target_color = Rgb([
-                (temp_color_alt.red * 255f32).floor() as u8,
-                (temp_color_alt.green * 255f32).floor() as u8,
-                (temp_color_alt.blue * 255f32).floor() as u8,
-            ]);
-        }
-        let target_pixel = Pixel {
-            position: working_canvas.starting_locations[index].clone(),
-            color: target_color,
+    // load and check the config, set output constraints, initialize canvas
+    let (config_yaml, config, mut painter): (StrictYaml, Config, Painter) =
+        match read_config(&arguments, resumed_checkpoint.as_ref()).and_then(|config_yaml| {
+            let config = Config::from_yaml(&config_yaml)?;
+            let painter = PaintingBuilder::from_config(&config).build()?;
+            return Ok((config_yaml, config, painter));
+        }) {
+            Ok(prepared) => prepared,
+            Err(error) => {
+                eprintln!("[ERROR] {}", error);
+                process::exit(1);
+            }
         };
-        place_pixel(&target_pixel, &mut working_canvas)
+    if arguments.command == Command::Validate {
+        let (x_size, y_size) = painter.size();
+        println!(
+            "{} is valid ({}x{} canvas)",
+            arguments.config_path, x_size, y_size
+        );
+        return;
     }
 
-    return working_canvas;
-}
-
-fn get_initial_locations(working_constraints: &Constraints) -> Vec<Coordinate> {
-    // hold starting locations
-    let mut starting_points = Vec::new();
+    // status messages go to stderr, stdout may carry a y4m stream
+    eprintln!("Painting with seed: {}", painter.seed());
 
-    // CUSTOMIZED:
-    for i in 0..working_constraints.y_size - 1 {
-        if (i + 1) % 64 == 0 {
-            starting_points.push(Coordinate { x: 256, y: i });
+    // continue from the checkpoint's state
+    if let Some(checkpoint) = &resumed_checkpoint {
+        if let Err(error) = checkpoint.restore(&mut painter) {
+            eprintln!("[ERROR] {}: {}", arguments.input_path, error);
+            process::exit(1);
         }
+        eprintln!(
+            "Resuming after {} placed pixels",
+            painter.stats().current_pixels_placed_count
+        );
     }
-    // starting_points.push(Coordinate { x: 256, y: 511 });
-    // starting_points.push(Coordinate { x: 1792, y: 0 });
-    return starting_points;
-}
 
-// write the output PNG files
-fn write_output_files(working_canvas: &Painting) {
-    // write the RGB painting file
-    working_canvas
-        .image
-        .save_with_format("./output/painting.png", ImageFormat::Png)
-        .unwrap();
-
-    // write the boundry region image
-    working_canvas
-        .boundry_region_image
-        .save_with_format("./output/boundry.png", ImageFormat::Png)
-        .unwrap();
-}
+    // make sure the output directory exists
+    let output_dir = Path::new(&arguments.output_dir);
+    fs::create_dir_all(output_dir).expect("[ERROR] unable to create output directory");
 
-// update a pixel on the canvas and add its neighbors to the boundry region
-fn place_pixel(target: &Pixel, working_canvas: &mut Painting) {
-    // update a pixel on the canvas
-    working_canvas
-        .image
-        .put_pixel(target.position.x, target.position.y, target.color);
+    // the painting's file name is fixed for the whole run, including resumed parts
+    let started: SystemTime = resumed_checkpoint
+        .as_ref()
+        .map_or_else(SystemTime::now, |checkpoint| checkpoint.started);
+    let painting_path = output_dir.join(config.output.file_name(painter.seed(), started));
+    let output_format = config.output.format;
 
-    // update counter
-    working_canvas.canvas_stats.current_pixels_placed_count += 1;
+    // every PNG carries the config that reproduces it
+    let config_text = metadata::resolved_config_text(&config_yaml, painter.seed());
 
-    // loop over neighbors in a 3x3 grid around the target
-    for i in 0..3 {
-        for j in 0..3 {
-            // skip self
-            if i == 1 && j == 1 {
-                continue;
-            }
-            // prevent less than zero out-of-bounds
-            if target.position.x == 0 && i == 0 {
-                continue;
-            }
-            if target.position.y == 0 && j == 0 {
-                continue;
-            }
-            // prevent greater than dimensions out-of-bounds
-            if target.position.x == (working_canvas.canvas_constraints.x_size - 1) && i == 2 {
-                continue;
-            }
-            if target.position.y == (working_canvas.canvas_constraints.y_size - 1) && j == 2 {
-                continue;
-            }
+    // initial update of the output files
+    output::write_output_files(
+        &painter,
+        output_dir,
+        &painting_path,
+        output_format,
+        &config_text,
+    )
+    .expect("[ERROR] unable to write the output files");
+
+    // record the growth every few placed pixels when an animation is configured
+    let mut animation_recorder: Option<AnimationRecorder> =
+        config.animation.clone().map(|options| {
+            AnimationRecorder::new(options, output_dir)
+                .expect("[ERROR] unable to create animation output")
+        });
+    record_frame(&mut animation_recorder, &painter);
+
+    // SIGINT and SIGTERM stop the loop after the current pixel, a second one exits right away
+    let stop_requested = Arc::new(AtomicBool::new(false));
+    let handler_stop_requested = Arc::clone(&stop_requested);
+    ctrlc::set_handler(move || {
+        if handler_stop_requested.swap(true, Ordering::SeqCst) {
+            process::exit(130);
+        }
+        eprintln!("Stopping, writing the output files");
+    })
+    .expect("[ERROR] unable to install the signal handler");
 
-            // calculate the neighbor's coordinate
-            let neighbor_x_coord: u32 = target.position.x + i - 1;
-            let neighbor_y_coord: u32 = target.position.y + j - 1;
+    // create a timer to update at regular intervals
+    let mut current_time = Instant::now();
+    let mut checkpoint_time = Instant::now();
 
-            // get the neigbor's luma (boundry region value)
-            let neighbor_luma: Luma<u8> = *working_canvas
-                .boundry_region_image
-                .get_pixel(neighbor_x_coord, neighbor_y_coord);
+    // run the simulation loop as long as there are available positions and colors
+    while !stop_requested.load(Ordering::SeqCst) && painter.step() {
+        record_frame(&mut animation_recorder, &painter);
 
-            // get the neighbor's color
-            let neighbor_color: Rgb<u8> = *working_canvas
-                .image
-                .get_pixel(neighbor_x_coord, neighbor_y_coord);
+        // update output files after given interval
+        if current_time.elapsed().as_secs_f32() > (1f32 / 2f32) {
+            current_time = Instant::now();
+            output::write_output_files(
+                &painter,
+                output_dir,
+                &painting_path,
+                output_format,
+                &config_text,
+            )
+            .expect("[ERROR] unable to write the output files");
+        }
 
-            // ensure locations are not added to the boundry region as duplicates
-            if neighbor_luma != Luma([0u8]) || neighbor_color != Rgb([0u8, 0u8, 0u8]) {
-                continue;
+        // write the whole state after the configured interval
+        if let Some(options) = &config.checkpoint {
+            if options
+                .interval
+                .is_some_and(|interval| checkpoint_time.elapsed() >= interval)
+            {
+                checkpoint_time = Instant::now();
+                checkpoint::write_checkpoint(
+                    &output_dir.join(&options.file_name),
+                    &painter,
+                    &config_text,
+                    started,
+                )
+                .expect("[ERROR] unable to write checkpoint");
             }
+        }
+    }
 
-            // add this neighbor to the boundry region LIST
-            working_canvas.boundry_region_list.push(Coordinate {
-                x: neighbor_x_coord,
-                y: neighbor_y_coord,
-            });
-            // add this neighbor to the boundry region IMAGE (luma)
-            working_canvas.boundry_region_image.put_pixel(
-                neighbor_x_coord,
-                neighbor_y_coord,
-                Luma([255u8]),
+    // final update of the output files
+    output::write_output_files(
+        &painter,
+        output_dir,
+        &painting_path,
+        output_format,
+        &config_text,
+    )
+    .expect("[ERROR] unable to write the output files");
+    output::write_placement_maps(&painter, output_dir)
+        .expect("[ERROR] unable to write the placement maps");
+    if let Some(recorder) = &mut animation_recorder {
+        recorder
+            .finish(painter.image(), painter.stats().current_pixels_placed_count)
+            .expect("[ERROR] unable to write animation");
+    }
+
+    // keep the state of a stopped painting so it can be finished later
+    let stopped = stop_requested.load(Ordering::SeqCst);
+    if let Some(options) = config.checkpoint.as_ref().filter(|options| options.on_stop) {
+        if stopped {
+            let checkpoint_path = output_dir.join(&options.file_name);
+            checkpoint::write_checkpoint(&checkpoint_path, &painter, &config_text, started)
+                .expect("[ERROR] unable to write checkpoint");
+            eprintln!(
+                "Continue with: rusty-color-shredder resume {}",
+                checkpoint_path.display()
             );
         }
     }
-}
 
-fn get_best_position_for_color(target_color: Rgb<u8>, working_canvas: &mut Painting) -> Pixel {
-    let (_best_value, best_position, best_position_index) = working_canvas
-        .boundry_region_list
-        .par_iter()
-        .enumerate()
-        .map(|available_location| {
-            evaluate_position(
-                available_location.1,
-                available_location.0,
-                &target_color,
-                &working_canvas.image,
-                &working_canvas.canvas_constraints,
-            )
-        })
-        .reduce_with(|a, b| {
-            if a.0 < b.0 {
-                return a;
-            } else if a.0 == b.0 && random::<bool>() {
-                return a;
-            } else {
-                return b;
-            }
-        })
-        .unwrap();
-
-    // remove target pixel from boundrry region IMAGE
-    working_canvas
-        .boundry_region_image
-        .put_pixel(best_position.x, best_position.y, Luma([0u8]));
+    eprintln!(
+        "{}: {} pixels placed in {:.1} s",
+        if stopped { "Stopped" } else { "Finished" },
+        painter.stats().current_pixels_placed_count,
+        painter.stats().start_time.elapsed().as_secs_f32()
+    );
+}
 
-    // if this is not the last location of the boundry region
-    // swap remove the target pixel location from the boundry region LIST
-    // (swap remove is much faster)
-    if working_canvas.boundry_region_list.len() > 1 {
-        return Pixel {
-            color: target_color,
-            position: working_canvas
-                .boundry_region_list
-                .swap_remove(best_position_index),
-        };
-    }
-    // for the last elemet remove normally
-    else {
-        return Pixel {
-            color: target_color,
-            position: working_canvas
-                .boundry_region_list
-                .remove(best_position_index),
-        };
+// hand the canvas to the animation recorder, which keeps a frame every frame_interval pixels
+fn record_frame(animation_recorder: &mut Option<AnimationRecorder>, painter: &Painter) {
+    if let Some(recorder) = animation_recorder {
+        recorder
+            .capture(painter.image(), painter.stats().current_pixels_placed_count)
+            .expect("[ERROR] unable to write animation frame");
     }
 }
 
-fn evaluate_position(
-    target_location: &Coordinate,
-    target_index: usize,
-    target_color: &Rgb<u8>,
-    canvas_image: &RgbImage,
-    canvas_constraints: &Constraints,
-) -> (f32, Coordinate, usize) {
-    let mut cummulative_color_distance: f32 = 0f32;
-    let mut neighbor_count: u64 = 0;
-    let mut color_distance: f32;
-    let _average_color_distance: f32;
-    let mut min_color_distance: f32 = f32::MAX;
-
-    // loop over neighbors in a 3x3 grid around the target
-    for i in 0..3 {
-        for j in 0..3 {
-            // skip self
-            if i == 1 && j == 1 {
-                continue;
-            }
-            // prevent less than zero out-of-bounds
-            if target_location.x == 0 && i == 0 {
-                continue;
-            }
-            if target_location.y == 0 && j == 0 {
-                continue;
-            }
-            // prevent greater than dimensions out-of-bounds
-            if target_location.x == (canvas_constraints.x_size - 1) && i == 2 {
-                continue;
-            }
-            if target_location.y == (canvas_constraints.y_size - 1) && j == 2 {
-                continue;
-            }
-
-            // calculate neighbor coordinates
-            let neighbor_x_coord: u32 = target_location.x + i - 1;
-            let neighbor_y_coord: u32 = target_location.y + j - 1;
-
-            // get color at neighbor's coordinates
-            let neighbor_color: Rgb<u8> =
-                *canvas_image.get_pixel(neighbor_x_coord, neighbor_y_coord);
-
-            // skip un-colored
-            if neighbor_color == Rgb([0u8, 0u8, 0u8]) {
-                continue;
-            }
-
-            // increment neighbor count
-            neighbor_count += 1;
-
-            //compute color distance
-            color_distance = 0f32;
-            for i in 0..3 {
-                color_distance +=
-                    (target_color[i] as f32 - neighbor_color[i] as f32).powf(2f32) as f32;
-            }
-            cummulative_color_distance += color_distance;
-
-            // update MIN
-            if color_distance < min_color_distance {
-                min_color_distance = color_distance;
-            } else if color_distance == min_color_distance && random::<bool>() {
-                min_color_distance = color_distance;
+// load the config file (or the config stored in a checkpoint) and apply command line overrides
+fn read_config(
+    arguments: &Arguments,
+    checkpoint: Option<&Checkpoint>,
+) -> Result<StrictYaml, ConfigError> {
+    let mut config = match checkpoint {
+        Some(checkpoint) => config::parse_config(&checkpoint.config_text, &arguments.input_path)?,
+        None => config::load_config(&arguments.config_path)?,
+    };
+    for (key_path, value) in arguments.overrides.iter() {
+        config::apply_override(&mut config, key_path, value).map_err(|message| {
+            ConfigError::Override {
+                key: key_path.clone(),
+                message,
             }
-        }
+        })?;
     }
-
-    // update AVG
-    _average_color_distance = cummulative_color_distance / neighbor_count as f32;
-    return (min_color_distance, *target_location, target_index);
+    return Ok(config);
 }
@@ -0,0 +1,129 @@
+use crate::config;
+use crate::png_chunks::{read_chunks, read_text_chunk, text_chunk, Chunk};
+use std::time::Duration;
+use strict_yaml_rust::strict_yaml::Hash;
+use strict_yaml_rust::{StrictYaml, StrictYamlEmitter};
+
+// keywords of the text chunks, Software is one of the keywords predefined by the PNG specification
+const SOFTWARE_KEYWORD: &str = "Software";
+const CONFIG_KEYWORD: &str = "Config";
+const SEED_KEYWORD: &str = "Seed";
+const PIXELS_KEYWORD: &str = "Pixels placed";
+const RUNTIME_KEYWORD: &str = "Runtime";
+
+// the config file a painting was made from, read back from its text chunks
+pub struct StoredConfig {
+    pub config_text: String,
+    // name and version of the program that wrote the painting, if stored
+    pub software: Option<String>,
+}
+
+// name and version of this program
+pub fn software() -> String {
+    return format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+}
+
+// a config file that reproduces the painting: the config block with every command line override
+// applied and the seed that was actually used
+pub fn resolved_config_text(config: &StrictYaml, seed: u64) -> String {
+    let mut resolved = config.clone();
+    config::apply_override(&mut resolved, "seed", &seed.to_string())
+        .expect("[ERROR] the config block is not a map");
+    let mut document = Hash::new();
+    document.insert(StrictYaml::String(String::from("config")), resolved);
+
+    let mut config_text = String::new();
+    StrictYamlEmitter::new(&mut config_text)
+        .dump(&StrictYaml::Hash(document))
+        .expect("[ERROR] unable to write the config");
+    config_text.push('\n');
+    return config_text;
+}
+
+// text chunks describing how a painting was made
+pub fn text_chunks(
+    config_text: &str,
+    seed: u64,
+    pixels_placed: u64,
+    runtime: Duration,
+) -> Vec<Chunk> {
+    return vec![
+        text_chunk(SOFTWARE_KEYWORD, &software()),
+        text_chunk(SEED_KEYWORD, &seed.to_string()),
+        text_chunk(PIXELS_KEYWORD, &pixels_placed.to_string()),
+        text_chunk(RUNTIME_KEYWORD, &format!("{:.3} s", runtime.as_secs_f64())),
+        text_chunk(CONFIG_KEYWORD, config_text),
+    ];
+}
+
+// read the config stored in a PNG file written by this program
+pub fn extract_config(bytes: &[u8]) -> Result<StoredConfig, String> {
+    let chunks = read_chunks(bytes).map_err(|error| error.to_string())?;
+    let mut config_text: Option<String> = None;
+    let mut software: Option<String> = None;
+    for (keyword, text) in chunks.iter().filter_map(read_text_chunk) {
+        match keyword.as_str() {
+            CONFIG_KEYWORD => config_text = Some(text),
+            SOFTWARE_KEYWORD => software = Some(text),
+            _ => {}
+        }
+    }
+    return match config_text {
+        Some(config_text) => Ok(StoredConfig {
+            config_text,
+            software,
+        }),
+        None => Err(String::from("no config is stored in this file")),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{self, CanvasSize, Config};
+    use crate::png_chunks::{insert_chunks, write_chunk, PNG_SIGNATURE};
+    use strict_yaml_rust::StrictYamlLoader;
+
+    #[test]
+    fn stored_config_reproduces_the_painting() {
+        let config_string = "config:
+  seed: \"random\"
+  canvas:
+    size:
+      x: \"12\"
+      y: \"8\"
+    starting_locations:
+      - x: 3
+        y: 4
+  colors:
+    generation_mode: \"random\"
+    generation_color_space: \"rgb\"
+";
+        let documents = StrictYamlLoader::load_from_str(config_string).unwrap();
+        let config_text = resolved_config_text(&documents[0]["config"], 42);
+
+        let mut png: Vec<u8> = PNG_SIGNATURE.to_vec();
+        write_chunk(&mut png, b"IHDR", &[0; 13]).unwrap();
+        write_chunk(&mut png, b"IEND", &[]).unwrap();
+        let chunks = text_chunks(&config_text, 42, 96, Duration::from_millis(1500));
+        let png = insert_chunks(&png, &chunks).unwrap();
+        let stored = extract_config(&png).unwrap();
+        assert_eq!(stored.config_text, config_text);
+        assert_eq!(stored.software, Some(software()));
+
+        // the seed is fixed, everything else is kept
+        let path = crate::tests::temp_path("extracted.yaml");
+        std::fs::write(&path, &stored.config_text).unwrap();
+        let yaml = config::load_config(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let config = Config::from_yaml(&yaml).unwrap();
+        assert_eq!(config.seed, Some(42));
+        assert!(matches!(
+            config.canvas.size,
+            CanvasSize::Fixed {
+                x_size: 12,
+                y_size: 8
+            }
+        ));
+    }
+}
@@ -0,0 +1,231 @@
+use crate::colors::rgb8_to_srgb;
+use image::Rgb;
+use palette::{ColorDifference, FromColor, Lab, Oklab};
+
+// how the color distances to a position's colored neighbors are combined into one score,
+// the position with the lowest score is chosen
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PlacementMetric {
+    // closest neighbor, grows thin "coral" like branches
+    Min,
+    // mean over all neighbors, grows smooth blobs
+    Average,
+    // furthest neighbor
+    Max,
+    // mean divided by the neighbor count, favors positions surrounded by more colored pixels
+    CountWeighted,
+    // median over all neighbors (mean of the middle two for an even count)
+    Median,
+    // mean over all neighbors weighted by the scoring neighborhood, closer neighbors count more
+    WeightedAverage,
+}
+
+impl PlacementMetric {
+    pub fn from_name(name: &str) -> Option<PlacementMetric> {
+        match name.to_lowercase().as_str() {
+            "min" => Some(PlacementMetric::Min),
+            "average" => Some(PlacementMetric::Average),
+            "max" => Some(PlacementMetric::Max),
+            "count_weighted" => Some(PlacementMetric::CountWeighted),
+            "median" => Some(PlacementMetric::Median),
+            "weighted_average" => Some(PlacementMetric::WeightedAverage),
+            _ => None,
+        }
+    }
+
+    // combine the neighbor distances into a score, positions without neighbors score f32::MAX,
+    // weights holds the weight of the neighbor at the same index and only counts for
+    // weighted_average
+    pub fn score(&self, distances: &mut [f32], weights: &[f32]) -> f32 {
+        if distances.is_empty() {
+            return f32::MAX;
+        }
+        let count = distances.len() as f32;
+        match self {
+            PlacementMetric::Min => distances.iter().copied().fold(f32::MAX, f32::min),
+            PlacementMetric::Average => distances.iter().sum::<f32>() / count,
+            PlacementMetric::Max => distances.iter().copied().fold(f32::MIN, f32::max),
+            PlacementMetric::CountWeighted => distances.iter().sum::<f32>() / (count * count),
+            PlacementMetric::Median => {
+                distances.sort_by(|a, b| a.total_cmp(b));
+                let middle = distances.len() / 2;
+                if distances.len().is_multiple_of(2) {
+                    (distances[middle - 1] + distances[middle]) / 2f32
+                } else {
+                    distances[middle]
+                }
+            }
+            PlacementMetric::WeightedAverage => {
+                let weighted_sum: f32 = distances.iter().zip(weights).map(|(d, w)| d * w).sum();
+                weighted_sum / weights.iter().sum::<f32>()
+            }
+        }
+    }
+}
+
+// how the distance between two colors is measured, colors are converted into the metric's space
+// once (see convert) and the converted values are compared with distance
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DistanceMetric {
+    // squared euclidean distance between 8-bit sRGB values
+    Rgb,
+    // CIE 1976 delta E, euclidean distance in CIELAB
+    Lab76,
+    // CIEDE2000 delta E, computed from CIELAB values
+    Ciede2000,
+    // euclidean distance in OKLab
+    Oklab,
+}
+
+impl DistanceMetric {
+    pub fn from_name(name: &str) -> Option<DistanceMetric> {
+        match name.to_lowercase().as_str() {
+            "rgb" => Some(DistanceMetric::Rgb),
+            "lab76" => Some(DistanceMetric::Lab76),
+            "ciede2000" => Some(DistanceMetric::Ciede2000),
+            "oklab" => Some(DistanceMetric::Oklab),
+            _ => None,
+        }
+    }
+
+    // convert an 8-bit color into the space the distance is measured in
+    pub fn convert(&self, color: Rgb<u8>) -> [f32; 3] {
+        match self {
+            DistanceMetric::Rgb => [color[0] as f32, color[1] as f32, color[2] as f32],
+            DistanceMetric::Lab76 | DistanceMetric::Ciede2000 => {
+                let lab = Lab::from_color(rgb8_to_srgb(color));
+                [lab.l, lab.a, lab.b]
+            }
+            DistanceMetric::Oklab => {
+                let oklab = Oklab::from_color(rgb8_to_srgb(color));
+                [oklab.l, oklab.a, oklab.b]
+            }
+        }
+    }
+
+    // distance between two converted colors
+    pub fn distance(&self, a: &[f32; 3], b: &[f32; 3]) -> f32 {
+        match self {
+            DistanceMetric::Rgb => squared_distance(a, b),
+            DistanceMetric::Lab76 | DistanceMetric::Oklab => squared_distance(a, b).sqrt(),
+            DistanceMetric::Ciede2000 => {
+                Lab::new(a[0], a[1], a[2]).get_color_difference(&Lab::new(b[0], b[1], b[2]))
+            }
+        }
+    }
+
+    // lower bound on the distance between two colors whose values differ by offset on one axis
+    pub fn axis_bound(&self, offset: f32) -> f32 {
+        match self {
+            DistanceMetric::Rgb => offset * offset,
+            DistanceMetric::Lab76 | DistanceMetric::Oklab => offset.abs(),
+            // CIEDE2000 is not bounded by a single CIELAB axis
+            DistanceMetric::Ciede2000 => 0f32,
+        }
+    }
+}
+
+// pseudo random key used to break ties between equally scored locations, the same salt and
+// location always give the same key so the winner does not depend on evaluation order
+pub fn tie_break_key(salt: u64, cell: usize) -> u64 {
+    // splitmix64 finalizer
+    let mut key = salt ^ (cell as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    key = (key ^ (key >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    key = (key ^ (key >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    return key ^ (key >> 31);
+}
+
+fn squared_distance(a: &[f32; 3], b: &[f32; 3]) -> f32 {
+    return (a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{} is not within {} of {}",
+            actual,
+            tolerance,
+            expected
+        );
+    }
+
+    #[test]
+    fn distances_match_published_values() {
+        let white = Rgb([255u8, 255, 255]);
+        let black = Rgb([0u8, 0, 0]);
+        let red = Rgb([255u8, 0, 0]);
+
+        let rgb = DistanceMetric::Rgb;
+        assert_eq!(
+            rgb.distance(&rgb.convert(red), &rgb.convert(black)),
+            65025f32
+        );
+
+        // sRGB red in CIELAB (D65)
+        let lab = DistanceMetric::Lab76;
+        let lab_red = lab.convert(red);
+        for (value, expected) in lab_red.iter().zip([53.2408f32, 80.0925, 67.2032]) {
+            assert_close(*value, expected, 0.01);
+        }
+        assert_close(
+            lab.distance(&lab.convert(white), &lab.convert(black)),
+            100f32,
+            0.01,
+        );
+
+        // sRGB red in OKLab, from the reference implementation
+        let oklab = DistanceMetric::Oklab;
+        for (value, expected) in oklab
+            .convert(red)
+            .iter()
+            .zip([0.62796f32, 0.22486, 0.12585])
+        {
+            assert_close(*value, expected, 0.001);
+        }
+        assert_close(
+            oklab.distance(&oklab.convert(white), &oklab.convert(black)),
+            1f32,
+            0.001,
+        );
+
+        // pairs 1, 7, 17 and 25 of Sharma, Wu and Dalal's CIEDE2000 test data
+        let ciede2000 = DistanceMetric::Ciede2000;
+        for (a, b, expected) in [
+            ([50f32, 2.6772, -79.7751], [50f32, 0.0, -82.7485], 2.0425f32),
+            ([50.0, 0.0, 0.0], [50.0, -1.0, 2.0], 2.3669),
+            ([50.0, 2.5, 0.0], [73.0, 25.0, -18.0], 27.1492),
+            (
+                [60.2574, -34.0099, 36.2677],
+                [60.4626, -34.1751, 39.4387],
+                1.2644,
+            ),
+        ] {
+            assert_close(ciede2000.distance(&a, &b), expected, 0.001);
+            assert_close(ciede2000.distance(&b, &a), expected, 0.001);
+        }
+    }
+
+    #[test]
+    fn metrics_combine_distances() {
+        let distances = [4f32, 1f32, 9f32, 2f32];
+        let weights = [1f32; 4];
+        let score = |metric: PlacementMetric, weights: &[f32]| {
+            return metric.score(&mut distances.clone(), weights);
+        };
+        assert_eq!(score(PlacementMetric::Min, &weights), 1f32);
+        assert_eq!(score(PlacementMetric::Average, &weights), 4f32);
+        assert_eq!(score(PlacementMetric::Max, &weights), 9f32);
+        assert_eq!(score(PlacementMetric::CountWeighted, &weights), 1f32);
+        assert_eq!(score(PlacementMetric::Median, &weights), 3f32);
+        assert_eq!(score(PlacementMetric::WeightedAverage, &weights), 4f32);
+        // (4 * 2 + 1 + 9 / 2 + 2 / 2) / (2 + 1 + 1 / 2 + 1 / 2)
+        let weights = [2f32, 1f32, 0.5f32, 0.5f32];
+        assert_eq!(score(PlacementMetric::WeightedAverage, &weights), 3.625f32);
+        assert_eq!(score(PlacementMetric::Average, &weights), 4f32);
+        assert_eq!(PlacementMetric::Average.score(&mut [], &[]), f32::MAX);
+    }
+}
@@ -0,0 +1,250 @@
+use crate::Coordinate;
+
+// kernels larger than this many rows or columns are rejected, every placed pixel visits every
+// offset of both neighborhoods
+pub const MAX_KERNEL_SIZE: usize = 15;
+
+// a neighbor's position relative to a location and how much it counts when scoring
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Offset {
+    pub x: i32,
+    pub y: i32,
+    pub weight: f32,
+}
+
+// how much a neighbor of a shape counts when scoring, by its distance from the location
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Falloff {
+    // every neighbor counts the same
+    Uniform,
+    // 1 / distance, diagonal neighbors count 1/√2
+    InverseDistance,
+    // exp(-distance² / 2σ²) for the given σ, smooths the square outline of larger discs
+    Gaussian(f32),
+}
+
+impl Falloff {
+    fn weight(&self, x: i32, y: i32) -> f32 {
+        let distance_squared = (x * x + y * y) as f32;
+        let weight = match self {
+            Falloff::Uniform => 1f32,
+            Falloff::InverseDistance => 1f32 / distance_squared.sqrt(),
+            Falloff::Gaussian(sigma) => (-distance_squared / (2f32 * sigma * sigma)).exp(),
+        };
+        // far neighbors of a narrow gaussian must still count a little, a location whose
+        // neighbors all weigh zero could not be scored
+        return weight.max(f32::MIN_POSITIVE);
+    }
+}
+
+// the locations around a location that count as its neighbors
+#[derive(Clone, PartialEq, Debug)]
+pub struct Neighborhood {
+    offsets: Vec<Offset>,
+}
+
+impl Neighborhood {
+    // the 4 edge neighbors, grows blocky diamonds
+    pub fn von_neumann() -> Neighborhood {
+        return Neighborhood::disc(1);
+    }
+
+    // the 8 locations of the 3x3 grid around a location
+    pub fn moore() -> Neighborhood {
+        return Neighborhood::disc_where(1, |_, _| true);
+    }
+
+    // every location within radius (euclidean) of a location
+    pub fn disc(radius: u32) -> Neighborhood {
+        let radius_squared = (radius * radius) as i32;
+        return Neighborhood::disc_where(radius as i32, |x, y| x * x + y * y <= radius_squared);
+    }
+
+    // a square matrix of weights centered on the location, zero entries (and the center) are
+    // not neighbors, returns an error message for malformed kernels
+    pub fn kernel(rows: &[Vec<f32>]) -> Result<Neighborhood, String> {
+        let size = rows.len();
+        if size.is_multiple_of(2) || size > MAX_KERNEL_SIZE {
+            return Err(format!(
+                "an odd number of rows up to {}, got {}",
+                MAX_KERNEL_SIZE, size
+            ));
+        }
+        if let Some(row) = rows.iter().find(|row| row.len() != size) {
+            return Err(format!(
+                "{} values in every row to make a square, got {}",
+                size,
+                row.len()
+            ));
+        }
+        if rows
+            .iter()
+            .flatten()
+            .any(|weight| !weight.is_finite() || *weight < 0f32)
+        {
+            return Err(String::from("finite weights of zero or more"));
+        }
+
+        let center = (size / 2) as i32;
+        let mut offsets: Vec<Offset> = Vec::new();
+        for (row_index, row) in rows.iter().enumerate() {
+            for (column_index, weight) in row.iter().enumerate() {
+                let (x, y) = (column_index as i32 - center, row_index as i32 - center);
+                if *weight > 0f32 && (x, y) != (0, 0) {
+                    offsets.push(Offset {
+                        x,
+                        y,
+                        weight: *weight,
+                    });
+                }
+            }
+        }
+        if offsets.is_empty() {
+            return Err(String::from(
+                "at least one weight above zero outside the center",
+            ));
+        }
+        return Ok(Neighborhood { offsets });
+    }
+
+    // the square of the given radius filtered by an accept function, without the center
+    fn disc_where(radius: i32, accept: impl Fn(i32, i32) -> bool) -> Neighborhood {
+        let mut offsets: Vec<Offset> = Vec::new();
+        for x in -radius..=radius {
+            for y in -radius..=radius {
+                if (x, y) != (0, 0) && accept(x, y) {
+                    offsets.push(Offset { x, y, weight: 1f32 });
+                }
+            }
+        }
+        return Neighborhood { offsets };
+    }
+
+    // the same neighbors weighted by their distance from the location
+    pub fn with_falloff(mut self, falloff: Falloff) -> Neighborhood {
+        for offset in self.offsets.iter_mut() {
+            offset.weight = falloff.weight(offset.x, offset.y);
+        }
+        return self;
+    }
+
+    // whether the neighbors of every location grown into with the growth neighborhood include
+    // the location it grew from, i.e. this neighborhood holds the mirror of every growth offset
+    pub fn reaches_back_along(&self, growth: &Neighborhood) -> bool {
+        return growth.offsets.iter().all(|grown| {
+            self.offsets
+                .iter()
+                .any(|offset| offset.x == -grown.x && offset.y == -grown.y)
+        });
+    }
+
+    pub fn offsets(&self) -> &[Offset] {
+        return &self.offsets;
+    }
+
+    pub fn len(&self) -> usize {
+        return self.offsets.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.offsets.is_empty();
+    }
+
+    // the neighbors of a location that lie on the canvas, with their weights
+    pub fn around(
+        &self,
+        location: Coordinate,
+        x_size: u32,
+        y_size: u32,
+    ) -> impl Iterator<Item = (Coordinate, f32)> + '_ {
+        return self.offsets.iter().filter_map(move |offset| {
+            shifted(location, offset.x, offset.y, x_size, y_size)
+                .map(|neighbor| (neighbor, offset.weight))
+        });
+    }
+
+    // the locations on the canvas that have the given location as a neighbor, these differ from
+    // the neighbors of the location for kernels that are not symmetric
+    pub fn reaching(
+        &self,
+        location: Coordinate,
+        x_size: u32,
+        y_size: u32,
+    ) -> impl Iterator<Item = (Coordinate, f32)> + '_ {
+        return self.offsets.iter().filter_map(move |offset| {
+            shifted(location, -offset.x, -offset.y, x_size, y_size)
+                .map(|neighbor| (neighbor, offset.weight))
+        });
+    }
+}
+
+// a location moved by an offset, None if it leaves the canvas
+fn shifted(location: Coordinate, x: i32, y: i32, x_size: u32, y_size: u32) -> Option<Coordinate> {
+    let shifted_x = location.x as i64 + x as i64;
+    let shifted_y = location.y as i64 + y as i64;
+    if shifted_x < 0 || shifted_y < 0 || shifted_x >= x_size as i64 || shifted_y >= y_size as i64 {
+        return None;
+    }
+    return Some(Coordinate {
+        x: shifted_x as u32,
+        y: shifted_y as u32,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shapes_have_the_expected_neighbors() {
+        assert_eq!(Neighborhood::von_neumann().len(), 4);
+        assert_eq!(Neighborhood::moore().len(), 8);
+        assert_eq!(Neighborhood::disc(2).len(), 12);
+
+        // corners only see the neighbors on the canvas
+        let corner = Coordinate { x: 0, y: 0 };
+        assert_eq!(Neighborhood::moore().around(corner, 4, 4).count(), 3);
+        assert_eq!(Neighborhood::disc(2).around(corner, 4, 4).count(), 5);
+
+        // a kernel that only reaches to the right
+        let kernel = Neighborhood::kernel(&[
+            vec![0f32, 0f32, 0f32],
+            vec![0f32, 9f32, 2f32],
+            vec![0f32, 0f32, 0f32],
+        ])
+        .unwrap();
+        let location = Coordinate { x: 1, y: 1 };
+        assert_eq!(
+            kernel.around(location, 4, 4).collect::<Vec<_>>(),
+            vec![(Coordinate { x: 2, y: 1 }, 2f32)]
+        );
+        assert_eq!(
+            kernel.reaching(location, 4, 4).collect::<Vec<_>>(),
+            vec![(Coordinate { x: 0, y: 1 }, 2f32)]
+        );
+
+        // diagonals are further away than edge neighbors
+        let weights: Vec<f32> = Neighborhood::moore()
+            .with_falloff(Falloff::InverseDistance)
+            .around(location, 4, 4)
+            .map(|(_, weight)| weight)
+            .collect();
+        assert_eq!(weights[0], std::f32::consts::FRAC_1_SQRT_2);
+        assert_eq!(weights[1], 1f32);
+        let gaussian = Neighborhood::disc(2).with_falloff(Falloff::Gaussian(1f32));
+        assert!(gaussian.offsets().iter().all(|offset| offset.weight < 1f32));
+
+        assert!(Neighborhood::kernel(&[vec![1f32, 1f32], vec![1f32, 1f32]]).is_err());
+        assert!(Neighborhood::kernel(&[vec![0f32; 3], vec![0f32, 1f32], vec![0f32; 3]]).is_err());
+        assert!(
+            Neighborhood::kernel(&[vec![0f32; 3], vec![0f32, 5f32, 0f32], vec![0f32; 3]]).is_err()
+        );
+        // an infinite weight would turn every weighted average into NaN
+        let infinite = [
+            vec![0f32, f32::INFINITY, 0f32],
+            vec![1f32, 0f32, 1f32],
+            vec![0f32; 3],
+        ];
+        assert!(Neighborhood::kernel(&infinite).is_err());
+    }
+}
@@ -0,0 +1,75 @@
+use std::{fs, io, path::Path};
+
+// element types that can be stored in a NPY file
+pub trait NpyElement: Copy {
+    // numpy dtype string, always little endian
+    const DESCR: &'static str;
+    fn write_le(self, bytes: &mut Vec<u8>);
+}
+
+impl NpyElement for u32 {
+    const DESCR: &'static str = "<u4";
+    fn write_le(self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl NpyElement for f32 {
+    const DESCR: &'static str = "<f4";
+    fn write_le(self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+// write a row major height x width array as a version 1.0 NPY file (numpy.load reads it as is)
+pub fn write_npy<T: NpyElement>(
+    path: &Path,
+    width: u32,
+    height: u32,
+    values: &[T],
+) -> io::Result<()> {
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': ({}, {}), }}",
+        T::DESCR,
+        height,
+        width
+    );
+
+    // the magic string, version and header length take 10 bytes, the header is padded with
+    // spaces and ends in a newline so the data starts on a 64 byte boundary
+    let padding = 63 - (10 + header.len()) % 64;
+    header.push_str(&" ".repeat(padding));
+    header.push('\n');
+
+    let mut bytes: Vec<u8> = Vec::with_capacity(10 + header.len() + values.len() * 4);
+    bytes.extend_from_slice(b"\x93NUMPY");
+    bytes.extend_from_slice(&[1u8, 0u8]);
+    bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(header.as_bytes());
+    for value in values {
+        value.write_le(&mut bytes);
+    }
+    return fs::write(path, bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_is_aligned_and_data_follows() {
+        let path = crate::tests::temp_path("header.npy");
+        write_npy(&path, 3, 2, &[1u32, 2, 3, 4, 5, u32::MAX]).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let header_length = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        let header = std::str::from_utf8(&bytes[10..10 + header_length]).unwrap();
+        assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+        assert_eq!((10 + header_length) % 64, 0);
+        assert!(header.starts_with("{'descr': '<u4', 'fortran_order': False, 'shape': (2, 3), }"));
+        assert!(header.ends_with('\n'));
+        assert_eq!(bytes.len(), 10 + header_length + 6 * 4);
+        assert_eq!(&bytes[bytes.len() - 4..], &[0xff; 4]);
+    }
+}
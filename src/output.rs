@@ -0,0 +1,488 @@
+use crate::colors::srgb_to_rgb8;
+use crate::png_chunks::{insert_chunks, Chunk};
+use crate::{metadata, npy, pixel_index, CellState, Painter, NOT_PLACED, NO_OWNER};
+use image::png::PngEncoder;
+use image::{ColorType, GrayImage, ImageBuffer, ImageFormat, Luma, Rgb, RgbImage};
+use image_webp::WebPEncoder;
+use palette::{FromColor, Hsv, Srgb};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// the painting with the full 16 bits per channel of every generated color
+pub type Rgb16Image = ImageBuffer<Rgb<u16>, Vec<u16>>;
+
+// how the final painting is stored
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum OutputFormat {
+    Png,
+    Tiff,
+    WebP,
+    Bmp,
+    Ppm,
+    Qoi,
+}
+
+// where and how the painting is written
+#[derive(Clone, Debug)]
+pub struct OutputOptions {
+    pub format: OutputFormat,
+    // 8 or 16 bits per channel, 16 bits are only written as png, tiff or ppm
+    pub bit_depth: u8,
+    // file name without the extension, {seed} and {timestamp} are replaced when writing
+    pub file_name: String,
+}
+
+impl OutputFormat {
+    pub fn from_name(name: &str) -> Option<OutputFormat> {
+        match name.to_lowercase().as_str() {
+            "png" => Some(OutputFormat::Png),
+            "tiff" | "tif" => Some(OutputFormat::Tiff),
+            "webp" => Some(OutputFormat::WebP),
+            "bmp" => Some(OutputFormat::Bmp),
+            "ppm" => Some(OutputFormat::Ppm),
+            "qoi" => Some(OutputFormat::Qoi),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::Tiff => "tiff",
+            OutputFormat::WebP => "webp",
+            OutputFormat::Bmp => "bmp",
+            OutputFormat::Ppm => "ppm",
+            OutputFormat::Qoi => "qoi",
+        }
+    }
+
+    pub fn supports_16_bit(self) -> bool {
+        return matches!(
+            self,
+            OutputFormat::Png | OutputFormat::Tiff | OutputFormat::Ppm
+        );
+    }
+}
+
+impl Default for OutputOptions {
+    fn default() -> OutputOptions {
+        return OutputOptions {
+            format: OutputFormat::Png,
+            bit_depth: 8,
+            file_name: String::from("painting"),
+        };
+    }
+}
+
+impl OutputOptions {
+    // the painting's file name with its placeholders filled in, started is when the painting
+    // started so every update of a run goes to the same file
+    pub fn file_name(&self, seed: u64, started: SystemTime) -> String {
+        let name = self
+            .file_name
+            .replace("{seed}", &seed.to_string())
+            .replace("{timestamp}", &format_timestamp(started));
+        return format!("{}.{}", name, self.format.extension());
+    }
+}
+
+// write the painting in the configured format and the PNG status images, PNG files store the
+// config along with the seed, the pixel count and the runtime in text chunks
+pub fn write_output_files(
+    working_canvas: &Painter,
+    output_dir: &Path,
+    painting_path: &Path,
+    output_format: OutputFormat,
+    config_text: &str,
+) -> io::Result<()> {
+    let text_chunks = metadata::text_chunks(
+        config_text,
+        working_canvas.seed,
+        working_canvas.canvas_stats.current_pixels_placed_count,
+        working_canvas.canvas_stats.start_time.elapsed(),
+    );
+
+    // write the RGB painting file
+    write_painting(
+        painting_path,
+        &working_canvas.image,
+        working_canvas.deep_image.as_ref(),
+        output_format,
+        &text_chunks,
+    )?;
+
+    // write the boundry region image (frontier white, blocked grey)
+    let boundry_region_image = GrayImage::from_fn(
+        working_canvas.canvas_constraints.x_size,
+        working_canvas.canvas_constraints.y_size,
+        |x, y| {
+            let cell_index = pixel_index(x, y, &working_canvas.canvas_constraints);
+            match working_canvas.occupancy[cell_index] {
+                CellState::Frontier => Luma([255u8]),
+                CellState::Blocked => Luma([128u8]),
+                CellState::Empty | CellState::Filled => Luma([0u8]),
+            }
+        },
+    );
+    write_png(
+        &output_dir.join("boundry.png"),
+        &boundry_region_image,
+        boundry_region_image.dimensions(),
+        ColorType::L8,
+        &text_chunks,
+    )?;
+
+    // write the region image, one color per seed (blocked grey, unreached black)
+    let region_image = RgbImage::from_fn(
+        working_canvas.canvas_constraints.x_size,
+        working_canvas.canvas_constraints.y_size,
+        |x, y| {
+            let cell_index = pixel_index(x, y, &working_canvas.canvas_constraints);
+            match (
+                working_canvas.occupancy[cell_index],
+                working_canvas.owners[cell_index],
+            ) {
+                (CellState::Blocked, _) => Rgb([128u8, 128u8, 128u8]),
+                (CellState::Filled, owner) if owner != NO_OWNER => region_color(owner),
+                _ => Rgb([0u8, 0u8, 0u8]),
+            }
+        },
+    );
+    write_png(
+        &output_dir.join("regions.png"),
+        &region_image,
+        region_image.dimensions(),
+        ColorType::Rgb8,
+        &text_chunks,
+    )?;
+    return Ok(());
+}
+
+// write when every pixel was placed, as 16-bit PNGs scaled to the full range (never placed
+// locations are 0, placed ones 1 - 65535) and as NPY arrays with the raw values (never placed
+// locations are 4294967295 in order.npy and NaN in timestamp.npy)
+pub fn write_placement_maps(working_canvas: &Painter, output_dir: &Path) -> io::Result<()> {
+    let x_size = working_canvas.canvas_constraints.x_size;
+    let y_size = working_canvas.canvas_constraints.y_size;
+    let scaled = |value: f32, max: f32| -> u16 {
+        return 1 + (value / max.max(f32::MIN_POSITIVE) * 65534f32).round() as u16;
+    };
+
+    // placement index
+    let last_order = working_canvas
+        .canvas_stats
+        .current_pixels_placed_count
+        .saturating_sub(1) as f32;
+    let order_image: ImageBuffer<Luma<u16>, Vec<u16>> =
+        ImageBuffer::from_fn(x_size, y_size, |x, y| {
+            match working_canvas.placement_order
+                [pixel_index(x, y, &working_canvas.canvas_constraints)]
+            {
+                NOT_PLACED => Luma([0u16]),
+                order => Luma([scaled(order as f32, last_order)]),
+            }
+        });
+    write_atomically(&output_dir.join("order.png"), |path| {
+        order_image
+            .save_with_format(path, ImageFormat::Png)
+            .map_err(io::Error::other)
+    })?;
+    write_atomically(&output_dir.join("order.npy"), |path| {
+        npy::write_npy(path, x_size, y_size, &working_canvas.placement_order)
+    })?;
+
+    // seconds since the painting started
+    let last_time = working_canvas
+        .placement_times
+        .iter()
+        .copied()
+        .filter(|time| !time.is_nan())
+        .fold(0f32, f32::max);
+    let timestamp_image: ImageBuffer<Luma<u16>, Vec<u16>> =
+        ImageBuffer::from_fn(x_size, y_size, |x, y| {
+            let time = working_canvas.placement_times
+                [pixel_index(x, y, &working_canvas.canvas_constraints)];
+            match time.is_nan() {
+                true => Luma([0u16]),
+                false => Luma([scaled(time, last_time)]),
+            }
+        });
+    write_atomically(&output_dir.join("timestamp.png"), |path| {
+        timestamp_image
+            .save_with_format(path, ImageFormat::Png)
+            .map_err(io::Error::other)
+    })?;
+    write_atomically(&output_dir.join("timestamp.npy"), |path| {
+        npy::write_npy(path, x_size, y_size, &working_canvas.placement_times)
+    })?;
+    return Ok(());
+}
+
+// a distinct color per region, hues are spread by the golden ratio so neighboring ids differ
+fn region_color(owner: u32) -> Rgb<u8> {
+    let hue = (owner as f32 * 0.618_034).fract() * 360f32;
+    return srgb_to_rgb8(Srgb::from_color(Hsv::new(hue, 0.65, 0.95)));
+}
+
+// write the painting, the 16-bit image is used instead of the 8-bit one when it is given and
+// the format can store it, text chunks are only stored in PNG files
+pub fn write_painting(
+    path: &Path,
+    image: &RgbImage,
+    deep_image: Option<&Rgb16Image>,
+    format: OutputFormat,
+    text_chunks: &[Chunk],
+) -> io::Result<()> {
+    return write_atomically(path, |temporary_path| {
+        write_painting_file(temporary_path, image, deep_image, format, text_chunks)
+    });
+}
+
+fn write_painting_file(
+    path: &Path,
+    image: &RgbImage,
+    deep_image: Option<&Rgb16Image>,
+    format: OutputFormat,
+    text_chunks: &[Chunk],
+) -> io::Result<()> {
+    let deep_image = deep_image.filter(|_| format.supports_16_bit());
+    let samples: &[u8] = image;
+    match format {
+        OutputFormat::Png => {
+            let (samples, color_type) = match deep_image {
+                // PNG stores 16-bit samples big endian
+                Some(deep_image) => (
+                    deep_image
+                        .iter()
+                        .flat_map(|value| value.to_be_bytes())
+                        .collect(),
+                    ColorType::Rgb16,
+                ),
+                None => (samples.to_vec(), ColorType::Rgb8),
+            };
+            let encoded = encode_png(
+                &samples,
+                (image.width(), image.height()),
+                color_type,
+                text_chunks,
+            )?;
+            return fs::write(path, encoded);
+        }
+        OutputFormat::Tiff | OutputFormat::Bmp => {
+            let image_format = match format {
+                OutputFormat::Tiff => ImageFormat::Tiff,
+                _ => ImageFormat::Bmp,
+            };
+            let result = match deep_image {
+                Some(deep_image) => deep_image.save_with_format(path, image_format),
+                None => image.save_with_format(path, image_format),
+            };
+            return result.map_err(io::Error::other);
+        }
+        OutputFormat::Ppm => {
+            let mut writer = BufWriter::new(File::create(path)?);
+            write_ppm(&mut writer, image, deep_image)?;
+            return writer.flush();
+        }
+        // lossless, WebP has no deeper color than 8 bits
+        OutputFormat::WebP => {
+            let writer = BufWriter::new(File::create(path)?);
+            return WebPEncoder::new(writer)
+                .encode(
+                    samples,
+                    image.width(),
+                    image.height(),
+                    image_webp::ColorType::Rgb8,
+                )
+                .map_err(io::Error::other);
+        }
+        OutputFormat::Qoi => {
+            let bytes = qoi::encode_to_vec(samples, image.width(), image.height())
+                .map_err(io::Error::other)?;
+            return fs::write(path, bytes);
+        }
+    }
+}
+
+// write a PNG file with the given text chunks after its header
+pub fn write_png(
+    path: &Path,
+    samples: &[u8],
+    size: (u32, u32),
+    color_type: ColorType,
+    text_chunks: &[Chunk],
+) -> io::Result<()> {
+    let encoded = encode_png(samples, size, color_type, text_chunks)?;
+    return write_atomically(path, |temporary_path| fs::write(temporary_path, encoded));
+}
+
+fn encode_png(
+    samples: &[u8],
+    (width, height): (u32, u32),
+    color_type: ColorType,
+    text_chunks: &[Chunk],
+) -> io::Result<Vec<u8>> {
+    let mut encoded: Vec<u8> = Vec::new();
+    PngEncoder::new(&mut encoded)
+        .encode(samples, width, height, color_type)
+        .map_err(io::Error::other)?;
+    return insert_chunks(&encoded, text_chunks);
+}
+
+// write a file under a temporary name next to it and rename it into place once complete, so
+// an interrupted run never leaves a partially written file behind
+pub fn write_atomically(
+    path: &Path,
+    write: impl FnOnce(&Path) -> io::Result<()>,
+) -> io::Result<()> {
+    let temporary_path = temporary_path(path);
+    if let Err(error) = write(&temporary_path) {
+        // the partial file is useless, failing to remove it does not change the outcome
+        fs::remove_file(&temporary_path).unwrap_or(());
+        return Err(error);
+    }
+    return fs::rename(&temporary_path, path);
+}
+
+// where write_atomically writes a file before renaming it into place, files written while
+// painting go here as well until they are complete
+pub fn temporary_path(path: &Path) -> PathBuf {
+    let mut temporary_path = path.as_os_str().to_owned();
+    temporary_path.push(".tmp");
+    return PathBuf::from(temporary_path);
+}
+
+// binary PPM (P6), 16-bit samples are stored big endian
+fn write_ppm(
+    writer: &mut impl Write,
+    image: &RgbImage,
+    deep_image: Option<&Rgb16Image>,
+) -> io::Result<()> {
+    match deep_image {
+        Some(deep_image) => {
+            write!(
+                writer,
+                "P6\n{} {}\n65535\n",
+                deep_image.width(),
+                deep_image.height()
+            )?;
+            for value in deep_image.iter() {
+                writer.write_all(&value.to_be_bytes())?;
+            }
+        }
+        None => {
+            write!(writer, "P6\n{} {}\n255\n", image.width(), image.height())?;
+            writer.write_all(image)?;
+        }
+    }
+    return Ok(());
+}
+
+// format a time as YYYYMMDD-HHMMSS in UTC
+fn format_timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+    let second_of_day = seconds % 86400;
+
+    // civil date from the days since 1970-01-01, counted in 400 year eras starting in March
+    let shifted_days = (seconds / 86400) as i64 + 719468;
+    let era = shifted_days.div_euclid(146097);
+    let day_of_era = shifted_days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    return format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        second_of_day / 3600,
+        second_of_day % 3600 / 60,
+        second_of_day % 60
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn file_name_placeholders_are_filled_in() {
+        let options = OutputOptions {
+            format: OutputFormat::Qoi,
+            bit_depth: 8,
+            file_name: String::from("shred-{seed}-{timestamp}"),
+        };
+        let started = UNIX_EPOCH + Duration::from_secs(1_709_210_096);
+        assert_eq!(
+            options.file_name(42, started),
+            "shred-42-20240229-123456.qoi"
+        );
+    }
+
+    #[test]
+    fn atomic_writes_only_replace_complete_files() {
+        let path = crate::tests::temp_path("atomic.txt");
+        let temporary_path = crate::tests::temp_path("atomic.txt.tmp");
+
+        write_atomically(&path, |temporary_path| {
+            fs::write(temporary_path, "complete")
+        })
+        .unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "complete");
+        assert!(!temporary_path.exists());
+
+        // a write failing half way keeps the previous file and leaves nothing else behind
+        let result = write_atomically(&path, |temporary_path| {
+            fs::write(temporary_path, "parti")?;
+            return Err(io::Error::other("disk full"));
+        });
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "complete");
+        assert!(!temporary_path.exists());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn formats_without_the_image_crate_decode_to_the_painting() {
+        let image = RgbImage::from_fn(5, 3, |x, y| Rgb([x as u8 * 50, y as u8 * 80, 7]));
+        let deep_image = Rgb16Image::from_fn(5, 3, |x, y| Rgb([x as u16 * 300, y as u16, 65535]));
+        let path = crate::tests::temp_path("decode.qoi");
+        write_painting(&path, &image, None, OutputFormat::Qoi, &[]).unwrap();
+        let (_, pixels) = qoi::decode_to_vec(fs::read(&path).unwrap()).unwrap();
+        assert_eq!(pixels, image.to_vec());
+        fs::remove_file(&path).unwrap();
+
+        let path = crate::tests::temp_path("decode.webp");
+        write_painting(&path, &image, Some(&deep_image), OutputFormat::WebP, &[]).unwrap();
+        let mut decoder =
+            image_webp::WebPDecoder::new(io::Cursor::new(fs::read(&path).unwrap())).unwrap();
+        let mut pixels = vec![0u8; decoder.output_buffer_size().unwrap()];
+        decoder.read_image(&mut pixels).unwrap();
+        assert_eq!(pixels, image.to_vec());
+        fs::remove_file(&path).unwrap();
+
+        let path = crate::tests::temp_path("decode.ppm");
+        write_painting(&path, &image, Some(&deep_image), OutputFormat::Ppm, &[]).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        assert!(bytes.starts_with(b"P6\n5 3\n65535\n"));
+        assert_eq!(bytes.len(), 13 + 5 * 3 * 3 * 2);
+        // the second pixel's red channel, big endian
+        assert_eq!(&bytes[19..21], &300u16.to_be_bytes());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,160 @@
+use std::io::{self, Write};
+
+// every PNG file starts with these bytes
+pub const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+
+// a chunk of a PNG file, the length and CRC are derived when it is written
+pub struct Chunk {
+    pub kind: [u8; 4],
+    pub data: Vec<u8>,
+}
+
+// split an encoded PNG file into its chunks
+pub fn read_chunks(bytes: &[u8]) -> io::Result<Vec<Chunk>> {
+    if bytes.len() < 8 || bytes[..8] != PNG_SIGNATURE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "missing PNG signature",
+        ));
+    }
+
+    let mut chunks: Vec<Chunk> = Vec::new();
+    let mut offset = 8;
+    while offset + 12 <= bytes.len() {
+        let length = u32::from_be_bytes([
+            bytes[offset],
+            bytes[offset + 1],
+            bytes[offset + 2],
+            bytes[offset + 3],
+        ]) as usize;
+        if offset + 12 + length > bytes.len() {
+            break;
+        }
+        let mut kind = [0u8; 4];
+        kind.copy_from_slice(&bytes[offset + 4..offset + 8]);
+        chunks.push(Chunk {
+            kind,
+            data: bytes[offset + 8..offset + 8 + length].to_vec(),
+        });
+        offset += 12 + length;
+    }
+    if offset != bytes.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "truncated PNG chunk",
+        ));
+    }
+    return Ok(chunks);
+}
+
+// write a chunk with its length and CRC
+pub fn write_chunk(writer: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(kind)?;
+    writer.write_all(data)?;
+    let crc = crc32_update(crc32_update(0xffff_ffff, kind), data) ^ 0xffff_ffff;
+    writer.write_all(&crc.to_be_bytes())?;
+    return Ok(());
+}
+
+// copy an encoded PNG file, adding extra chunks right after the header
+pub fn insert_chunks(bytes: &[u8], extra_chunks: &[Chunk]) -> io::Result<Vec<u8>> {
+    let chunks = read_chunks(bytes)?;
+    let mut output: Vec<u8> = PNG_SIGNATURE.to_vec();
+    for (index, chunk) in chunks.iter().enumerate() {
+        write_chunk(&mut output, &chunk.kind, &chunk.data)?;
+        if index == 0 {
+            for extra in extra_chunks {
+                write_chunk(&mut output, &extra.kind, &extra.data)?;
+            }
+        }
+    }
+    return Ok(output);
+}
+
+// a text chunk, tEXt when the text is plain ASCII and uncompressed iTXt (UTF-8) otherwise
+pub fn text_chunk(keyword: &str, text: &str) -> Chunk {
+    let mut data: Vec<u8> = keyword.as_bytes().to_vec();
+    data.push(0);
+    if text.is_ascii() {
+        data.extend_from_slice(text.as_bytes());
+        return Chunk {
+            kind: *b"tEXt",
+            data,
+        };
+    }
+
+    // no compression, empty language tag and translated keyword
+    data.extend_from_slice(&[0, 0, 0, 0]);
+    data.extend_from_slice(text.as_bytes());
+    return Chunk {
+        kind: *b"iTXt",
+        data,
+    };
+}
+
+// the keyword and text of a tEXt or uncompressed iTXt chunk, None for any other chunk
+pub fn read_text_chunk(chunk: &Chunk) -> Option<(String, String)> {
+    let separator = chunk.data.iter().position(|byte| *byte == 0)?;
+    // keywords and tEXt are Latin-1, whose code points match the first 256 of unicode
+    let keyword: String = chunk.data[..separator]
+        .iter()
+        .map(|byte| *byte as char)
+        .collect();
+    let rest = &chunk.data[separator + 1..];
+    match &chunk.kind {
+        b"tEXt" => return Some((keyword, rest.iter().map(|byte| *byte as char).collect())),
+        b"iTXt" => {
+            // skip the compression flag and method, the language tag and the translated keyword
+            if rest.len() < 2 || rest[0] != 0 {
+                return None;
+            }
+            let mut text = &rest[2..];
+            for _ in 0..2 {
+                let end = text.iter().position(|byte| *byte == 0)?;
+                text = &text[end + 1..];
+            }
+            return String::from_utf8(text.to_vec())
+                .ok()
+                .map(|text| (keyword, text));
+        }
+        _ => return None,
+    }
+}
+
+// the CRC-32 (IEEE) used by PNG chunks, computed bit by bit from a running value
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    return crc;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_round_trip_with_a_valid_crc() {
+        let mut bytes: Vec<u8> = PNG_SIGNATURE.to_vec();
+        write_chunk(&mut bytes, b"IEND", &[]).unwrap();
+        // the IEND chunk is the same in every PNG file
+        assert_eq!(
+            &bytes[8..],
+            &[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]
+        );
+
+        write_chunk(&mut bytes, b"tEXt", b"key\0value").unwrap();
+        let chunks = read_chunks(&bytes).unwrap();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(&chunks[1].kind, b"tEXt");
+        assert_eq!(chunks[1].data, b"key\0value");
+    }
+}
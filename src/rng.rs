@@ -0,0 +1,66 @@
+use rand::{Error, RngCore};
+
+// xoshiro256++ generator, small, fast and its raw sequence is identical on every platform, values
+// drawn through rand's helpers (gen, gen_range, shuffle) are only reproducible within the rand
+// version pinned in Cargo.toml
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PaintRng {
+    state: [u64; 4],
+}
+
+impl PaintRng {
+    // expand a 64-bit seed into the full state using splitmix64
+    pub fn from_seed(seed: u64) -> PaintRng {
+        let mut mix = seed;
+        let mut state = [0u64; 4];
+        for value in state.iter_mut() {
+            mix = mix.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = mix;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            *value = z ^ (z >> 31);
+        }
+        return PaintRng { state };
+    }
+
+    // the full generator state, restoring it continues the exact same sequence
+    pub fn state(&self) -> [u64; 4] {
+        return self.state;
+    }
+
+    pub fn from_state(state: [u64; 4]) -> PaintRng {
+        return PaintRng { state };
+    }
+}
+
+impl RngCore for PaintRng {
+    fn next_u64(&mut self) -> u64 {
+        let result = (self.state[0].wrapping_add(self.state[3]))
+            .rotate_left(23)
+            .wrapping_add(self.state[0]);
+        let t = self.state[1] << 17;
+        self.state[2] ^= self.state[0];
+        self.state[3] ^= self.state[1];
+        self.state[1] ^= self.state[2];
+        self.state[0] ^= self.state[3];
+        self.state[2] ^= t;
+        self.state[3] = self.state[3].rotate_left(45);
+        return result;
+    }
+
+    fn next_u32(&mut self) -> u32 {
+        return (self.next_u64() >> 32) as u32;
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        return Ok(());
+    }
+}
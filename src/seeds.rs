@@ -0,0 +1,480 @@
+use crate::colors::{self, rgb8_to_rgb16, ColorConfig, ColorSource};
+use crate::rng::PaintRng;
+use crate::Coordinate;
+use image::Rgb;
+use rand::Rng;
+use std::f32::consts::TAU;
+
+// how the colors of the locations seeded by one entry are chosen, without either option the
+// main color source is used
+#[derive(Clone, Default)]
+pub struct SeedColors {
+    pub fixed_color: Option<Rgb<u8>>,
+    pub generator: Option<ColorConfig>,
+}
+
+// one starting_locations or seeds entry from config
+#[derive(Clone)]
+pub struct SeedEntry {
+    pub pattern: SeedPattern,
+    pub colors: SeedColors,
+}
+
+// a resolved starting location and the index of the entry that produced it
+#[derive(Copy, Clone)]
+pub struct Seed {
+    pub position: Coordinate,
+    pub entry: usize,
+}
+
+// the colors of one seed entry
+pub struct SeedColorSource {
+    fixed_color: Option<Rgb<u8>>,
+    source: Option<Box<dyn ColorSource>>,
+}
+
+// a declarative way of placing starting locations, every pattern is resolved against the final
+// canvas size and generated points outside the canvas are dropped
+#[derive(Clone, Debug, PartialEq)]
+pub enum SeedPattern {
+    // a single explicit location, it must lie on the canvas
+    Point(Coordinate),
+    // a regular grid, the first point sits at the offset
+    Grid {
+        spacing_x: u32,
+        spacing_y: u32,
+        offset_x: u32,
+        offset_y: u32,
+    },
+    // count points drawn uniformly over the canvas
+    Random {
+        count: u32,
+    },
+    // count points evenly spaced around an ellipse (or a circle when both radii match),
+    // centered on the canvas unless a center is given
+    Ellipse {
+        center: Option<(f32, f32)>,
+        radius_x: f32,
+        radius_y: f32,
+        count: u32,
+    },
+    // count points evenly spaced from start to end, both ends included
+    Line {
+        start: (f32, f32),
+        end: (f32, f32),
+        count: u32,
+    },
+    // points at least min_distance apart filling the canvas (Bridson's algorithm)
+    PoissonDisk {
+        min_distance: f32,
+        attempts: u32,
+    },
+    // the middle of the canvas
+    Center,
+    // points every spacing pixels along all four edges
+    Edges {
+        spacing: u32,
+    },
+    // the four corners
+    Corners,
+}
+
+impl SeedPattern {
+    // the starting locations this pattern produces on a canvas of the given size
+    pub fn generate(&self, x_size: u32, y_size: u32, rng: &mut PaintRng) -> Vec<Coordinate> {
+        let mut points: Vec<Coordinate> = Vec::new();
+        match *self {
+            SeedPattern::Point(location) => {
+                if location.x < x_size && location.y < y_size {
+                    points.push(location);
+                }
+            }
+            SeedPattern::Grid {
+                spacing_x,
+                spacing_y,
+                offset_x,
+                offset_y,
+            } => {
+                for y in (offset_y..y_size).step_by(spacing_y as usize) {
+                    for x in (offset_x..x_size).step_by(spacing_x as usize) {
+                        points.push(Coordinate { x, y });
+                    }
+                }
+            }
+            SeedPattern::Random { count } => {
+                for _ in 0..count {
+                    points.push(Coordinate {
+                        x: rng.gen_range(0..x_size),
+                        y: rng.gen_range(0..y_size),
+                    });
+                }
+            }
+            SeedPattern::Ellipse {
+                center,
+                radius_x,
+                radius_y,
+                count,
+            } => {
+                let (center_x, center_y) =
+                    center.unwrap_or(((x_size - 1) as f32 / 2f32, (y_size - 1) as f32 / 2f32));
+                for i in 0..count {
+                    let angle = TAU * i as f32 / count as f32;
+                    push_point(
+                        &mut points,
+                        center_x + radius_x * angle.cos(),
+                        center_y + radius_y * angle.sin(),
+                        x_size,
+                        y_size,
+                    );
+                }
+            }
+            SeedPattern::Line { start, end, count } => {
+                for i in 0..count {
+                    // a single point sits at the start
+                    let t = if count > 1 {
+                        i as f32 / (count - 1) as f32
+                    } else {
+                        0f32
+                    };
+                    push_point(
+                        &mut points,
+                        start.0 + (end.0 - start.0) * t,
+                        start.1 + (end.1 - start.1) * t,
+                        x_size,
+                        y_size,
+                    );
+                }
+            }
+            SeedPattern::PoissonDisk {
+                min_distance,
+                attempts,
+            } => {
+                for (x, y) in poisson_disk(x_size, y_size, min_distance, attempts, rng) {
+                    points.push(Coordinate {
+                        x: x as u32,
+                        y: y as u32,
+                    });
+                }
+            }
+            SeedPattern::Center => points.push(Coordinate {
+                x: (x_size - 1) / 2,
+                y: (y_size - 1) / 2,
+            }),
+            SeedPattern::Edges { spacing } => {
+                for x in (0..x_size).step_by(spacing as usize) {
+                    points.push(Coordinate { x, y: 0 });
+                    points.push(Coordinate { x, y: y_size - 1 });
+                }
+                for y in (0..y_size).step_by(spacing as usize) {
+                    points.push(Coordinate { x: 0, y });
+                    points.push(Coordinate { x: x_size - 1, y });
+                }
+            }
+            SeedPattern::Corners => {
+                for (x, y) in [
+                    (0, 0),
+                    (x_size - 1, 0),
+                    (0, y_size - 1),
+                    (x_size - 1, y_size - 1),
+                ] {
+                    points.push(Coordinate { x, y });
+                }
+            }
+        }
+        return points;
+    }
+}
+
+impl SeedColorSource {
+    // build the entry's own color source, if it has one
+    pub fn new(colors: &SeedColors, rng: &mut PaintRng) -> SeedColorSource {
+        return SeedColorSource {
+            fixed_color: colors.fixed_color,
+            source: colors
+                .generator
+                .as_ref()
+                .map(|config| colors::color_source(config, rng)),
+        };
+    }
+
+    // the color of the seed itself when the entry fixes it
+    pub fn fixed_color(&self) -> Option<Rgb<u16>> {
+        return self.fixed_color.map(rgb8_to_rgb16);
+    }
+
+    // the source every other color of the entry is drawn from, the entry's own colors win over
+    // the shared ones (a fixed color only applies to the seed itself)
+    pub fn source<'a>(&'a mut self, shared: &'a mut dyn ColorSource) -> &'a mut dyn ColorSource {
+        match &mut self.source {
+            Some(source) => return source.as_mut(),
+            None => return shared,
+        }
+    }
+
+    // how many colors the entry's own source has handed out, see ColorSource::position
+    pub fn position(&self) -> usize {
+        return self.source.as_ref().map_or(0, |source| source.position());
+    }
+
+    pub fn set_position(&mut self, position: usize) {
+        if let Some(source) = &mut self.source {
+            source.set_position(position);
+        }
+    }
+}
+
+// resolve every entry into starting locations, keeping the first occurrence of each location so
+// overlapping entries do not seed a location twice
+pub fn resolve_starting_locations(
+    entries: &[SeedEntry],
+    x_size: u32,
+    y_size: u32,
+    rng: &mut PaintRng,
+) -> Vec<Seed> {
+    let mut seen: Vec<bool> = vec![false; x_size as usize * y_size as usize];
+    let mut starting_locations: Vec<Seed> = Vec::new();
+    for (entry, seed_entry) in entries.iter().enumerate() {
+        for position in seed_entry.pattern.generate(x_size, y_size, rng) {
+            let index = position.x as usize + position.y as usize * x_size as usize;
+            if !seen[index] {
+                seen[index] = true;
+                starting_locations.push(Seed { position, entry });
+            }
+        }
+    }
+    return starting_locations;
+}
+
+// round a point to the nearest location, dropping it if it falls outside the canvas
+fn push_point(points: &mut Vec<Coordinate>, x: f32, y: f32, x_size: u32, y_size: u32) {
+    let (x, y) = (x.round(), y.round());
+    if x < 0f32 || y < 0f32 || x >= x_size as f32 || y >= y_size as f32 {
+        return;
+    }
+    points.push(Coordinate {
+        x: x as u32,
+        y: y as u32,
+    });
+}
+
+// Bridson's poisson disk sampling over a x_size by y_size area
+fn poisson_disk(
+    x_size: u32,
+    y_size: u32,
+    min_distance: f32,
+    attempts: u32,
+    rng: &mut PaintRng,
+) -> Vec<(f32, f32)> {
+    // each background grid cell can hold at most one sample
+    let cell_size = min_distance / 2f32.sqrt();
+    let grid_width = (x_size as f32 / cell_size).ceil() as usize;
+    let grid_height = (y_size as f32 / cell_size).ceil() as usize;
+    let mut grid: Vec<Option<usize>> = vec![None; grid_width * grid_height];
+    let grid_cell = |x: f32, y: f32| -> (usize, usize) {
+        return ((x / cell_size) as usize, (y / cell_size) as usize);
+    };
+
+    let mut samples: Vec<(f32, f32)> = Vec::new();
+    let mut active: Vec<usize> = Vec::new();
+
+    // start from a random point
+    let first = (
+        rng.gen::<f32>() * x_size as f32,
+        rng.gen::<f32>() * y_size as f32,
+    );
+    let (cell_x, cell_y) = grid_cell(first.0, first.1);
+    grid[cell_y * grid_width + cell_x] = Some(0);
+    samples.push(first);
+    active.push(0);
+
+    while !active.is_empty() {
+        let active_index = rng.gen_range(0..active.len());
+        let origin = samples[active[active_index]];
+        let mut found = false;
+
+        // try candidates in the annulus between min_distance and twice min_distance
+        for _ in 0..attempts {
+            let angle = rng.gen::<f32>() * TAU;
+            let radius = min_distance * (1f32 + rng.gen::<f32>());
+            let candidate = (
+                origin.0 + radius * angle.cos(),
+                origin.1 + radius * angle.sin(),
+            );
+            if candidate.0 < 0f32
+                || candidate.1 < 0f32
+                || candidate.0 >= x_size as f32
+                || candidate.1 >= y_size as f32
+            {
+                continue;
+            }
+
+            // only the surrounding 5x5 grid cells can hold a sample closer than min_distance
+            let (cell_x, cell_y) = grid_cell(candidate.0, candidate.1);
+            let mut too_close = false;
+            for neighbor_y in cell_y.saturating_sub(2)..(cell_y + 3).min(grid_height) {
+                for neighbor_x in cell_x.saturating_sub(2)..(cell_x + 3).min(grid_width) {
+                    if let Some(sample) = grid[neighbor_y * grid_width + neighbor_x] {
+                        let (sample_x, sample_y) = samples[sample];
+                        let distance_squared =
+                            (sample_x - candidate.0).powi(2) + (sample_y - candidate.1).powi(2);
+                        if distance_squared < min_distance * min_distance {
+                            too_close = true;
+                        }
+                    }
+                }
+            }
+            if too_close {
+                continue;
+            }
+
+            grid[cell_y * grid_width + cell_x] = Some(samples.len());
+            active.push(samples.len());
+            samples.push(candidate);
+            found = true;
+            break;
+        }
+
+        // a sample with no room left around it stops spawning candidates
+        if !found {
+            active.swap_remove(active_index);
+        }
+    }
+    return samples;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colors::{
+        rgb16_to_rgb8, ChannelOptions, ColorOptions, ColorSpace, Palette, PaletteOptions,
+    };
+
+    fn locations(pattern: SeedPattern, x_size: u32, y_size: u32) -> Vec<(u32, u32)> {
+        let mut rng = PaintRng::from_seed(7);
+        return pattern
+            .generate(x_size, y_size, &mut rng)
+            .iter()
+            .map(|location| (location.x, location.y))
+            .collect();
+    }
+
+    #[test]
+    fn fixed_patterns() {
+        let grid = SeedPattern::Grid {
+            spacing_x: 4,
+            spacing_y: 5,
+            offset_x: 2,
+            offset_y: 0,
+        };
+        assert_eq!(locations(grid, 8, 6), vec![(2, 0), (6, 0), (2, 5), (6, 5)]);
+        let line = SeedPattern::Line {
+            start: (0f32, 0f32),
+            end: (6f32, 3f32),
+            count: 3,
+        };
+        assert_eq!(locations(line, 8, 6), vec![(0, 0), (3, 2), (6, 3)]);
+        assert_eq!(locations(SeedPattern::Center, 8, 6), vec![(3, 2)]);
+        assert_eq!(
+            locations(SeedPattern::Corners, 8, 6),
+            vec![(0, 0), (7, 0), (0, 5), (7, 5)]
+        );
+    }
+
+    #[test]
+    fn points_off_the_canvas_are_dropped() {
+        let circle = SeedPattern::Ellipse {
+            center: Some((0f32, 0f32)),
+            radius_x: 4f32,
+            radius_y: 4f32,
+            count: 4,
+        };
+        assert_eq!(locations(circle, 8, 8), vec![(4, 0), (0, 4)]);
+    }
+
+    #[test]
+    fn poisson_disk_points_keep_their_distance() {
+        let samples = poisson_disk(64, 48, 6f32, 30, &mut PaintRng::from_seed(3));
+        assert!(samples.len() > 20);
+        for (i, a) in samples.iter().enumerate() {
+            for b in &samples[i + 1..] {
+                assert!((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2) >= 36f32);
+            }
+        }
+    }
+
+    #[test]
+    fn seed_colors_take_precedence_over_the_main_source() {
+        let mut rng = PaintRng::from_seed(0);
+        let mut main_source = Palette {
+            colors: vec![Rgb([1, 2, 3])],
+            index: 0,
+        };
+        let own_palette = ColorConfig::Palette(PaletteOptions {
+            color_options: ColorOptions {
+                color_space: ColorSpace::Rgb,
+                channels: [ChannelOptions {
+                    lock: Some(0f32),
+                    min: 0f32,
+                    max: 1f32,
+                }; 3],
+            },
+            bit_depth: 1,
+            group_by_channel: 1,
+            shuffle: false,
+        });
+
+        let mut fixed = SeedColorSource::new(
+            &SeedColors {
+                fixed_color: Some(Rgb([255, 128, 0])),
+                generator: Some(own_palette.clone()),
+            },
+            &mut rng,
+        );
+        let mut own = SeedColorSource::new(
+            &SeedColors {
+                fixed_color: None,
+                generator: Some(own_palette),
+            },
+            &mut rng,
+        );
+        let mut shared = SeedColorSource::new(&SeedColors::default(), &mut rng);
+
+        let mut next = |entry: &mut SeedColorSource| {
+            entry
+                .fixed_color()
+                .or_else(|| entry.source(&mut main_source).next_color(&mut rng))
+                .map(rgb16_to_rgb8)
+        };
+        assert_eq!(next(&mut fixed), Some(Rgb([255, 128, 0])));
+        assert_eq!(next(&mut own), Some(Rgb([0, 0, 0])));
+        assert_eq!(next(&mut own), None);
+        assert_eq!(next(&mut shared), Some(Rgb([1, 2, 3])));
+        assert_eq!(next(&mut shared), None);
+    }
+
+    #[test]
+    fn overlapping_patterns_seed_once() {
+        let entries: Vec<SeedEntry> = [
+            SeedPattern::Point(Coordinate { x: 0, y: 0 }),
+            SeedPattern::Corners,
+            SeedPattern::Edges { spacing: 7 },
+        ]
+        .into_iter()
+        .map(|pattern| SeedEntry {
+            pattern,
+            colors: SeedColors::default(),
+        })
+        .collect();
+        let starting_locations =
+            resolve_starting_locations(&entries, 8, 8, &mut PaintRng::from_seed(0));
+        let entry_of = |x: u32, y: u32| {
+            starting_locations
+                .iter()
+                .find(|seed| (seed.position.x, seed.position.y) == (x, y))
+                .map(|seed| seed.entry)
+        };
+        assert_eq!(starting_locations.len(), 4);
+        assert_eq!(entry_of(0, 0), Some(0));
+        assert_eq!(entry_of(7, 7), Some(1));
+    }
+}
@@ -0,0 +1,280 @@
+use crate::config::SelectorKind;
+use crate::metrics::{tie_break_key, DistanceMetric};
+use crate::spatial_index::ColorIndex;
+use crate::{evaluate_position, pixel_index, Coordinate, Painter};
+use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
+
+// finds the boundry region location a color is placed at, place_pixel reports every change of
+// the boundry region so selectors can keep their own structures up to date, new strategies are
+// painted with through PaintingBuilder::custom_selector
+pub trait PositionSelector: Send + Sync {
+    // a location joined the boundry region
+    fn frontier_added(&mut self, _cell: usize) {}
+
+    // a location left the boundry region because it was painted
+    fn frontier_removed(&mut self, _cell: usize) {}
+
+    // a boundry region location gained a painted neighbor, its color converted for the
+    // distance metric
+    fn neighbor_painted(&mut self, _cell: usize, _color: [f32; 3]) {}
+
+    // forget every event, the current boundry region is reported again afterwards
+    fn clear(&mut self) {}
+
+    // the best location for a color (converted for the distance metric) among the candidates,
+    // None if no candidate is accepted
+    fn select(&self, target_color: &[f32; 3], candidates: &Candidates) -> Option<usize>;
+}
+
+// the boundry region locations a color may be placed at and how they are scored
+pub struct Candidates<'a> {
+    pub(crate) painter: &'a Painter,
+    // only locations of this region are accepted when set
+    pub(crate) region: Option<u32>,
+    pub(crate) tie_salt: u64,
+}
+
+// scores every location in the boundry region
+pub struct BruteForceSelector;
+
+// nearest neighbor search over the painted neighbors of every boundry region location, only
+// answers min queries for the distances ColorIndex supports
+pub struct IndexedSelector {
+    color_index: ColorIndex,
+    distance_metric: DistanceMetric,
+}
+
+impl Candidates<'_> {
+    // every location of the boundry region, including ones outside the region
+    pub fn frontier(&self) -> &[Coordinate] {
+        return &self.painter.boundry_region_list;
+    }
+
+    // index of a location in the per-pixel canvas buffers
+    pub fn cell(&self, location: &Coordinate) -> usize {
+        return pixel_index(location.x, location.y, &self.painter.canvas_constraints);
+    }
+
+    // whether the color may be placed at a boundry region location
+    pub fn accepts(&self, cell: usize) -> bool {
+        return self
+            .region
+            .is_none_or(|region| self.painter.owners[cell] == region);
+    }
+
+    // the placement metric over the painted neighbors of a location, lower is better
+    pub fn score(&self, location: &Coordinate, target_color: &[f32; 3]) -> f32 {
+        let (score, _) = evaluate_position(location, target_color, self.painter);
+        return score;
+    }
+
+    // ranks equally scored locations, lower wins and the location breaks remaining ties
+    pub fn tie_break_key(&self, cell: usize) -> u64 {
+        return tie_break_key(self.tie_salt, cell);
+    }
+
+    pub fn tie_salt(&self) -> u64 {
+        return self.tie_salt;
+    }
+}
+
+impl PositionSelector for BruteForceSelector {
+    fn select(&self, target_color: &[f32; 3], candidates: &Candidates) -> Option<usize> {
+        return candidates
+            .frontier()
+            .par_iter()
+            .map(|location| (location, candidates.cell(location)))
+            .filter(|(_, cell)| candidates.accepts(*cell))
+            .map(|(location, cell)| {
+                let score = candidates.score(location, target_color);
+                (score, candidates.tie_break_key(cell), cell)
+            })
+            // a strict total order, so the winner does not depend on how rayon splits the work
+            .reduce_with(|a, b| {
+                if a.0 < b.0 || (a.0 == b.0 && (a.1, a.2) < (b.1, b.2)) {
+                    return a;
+                } else {
+                    return b;
+                }
+            })
+            .map(|(_, _, cell)| cell);
+    }
+}
+
+impl IndexedSelector {
+    // an empty index for a canvas with the given number of locations
+    pub fn new(distance_metric: DistanceMetric, cell_count: usize) -> IndexedSelector {
+        return IndexedSelector {
+            color_index: ColorIndex::new(distance_metric, cell_count),
+            distance_metric,
+        };
+    }
+}
+
+impl PositionSelector for IndexedSelector {
+    fn frontier_removed(&mut self, cell: usize) {
+        self.color_index.remove(cell);
+    }
+
+    fn neighbor_painted(&mut self, cell: usize, color: [f32; 3]) {
+        self.color_index.insert(color, cell);
+    }
+
+    fn clear(&mut self) {
+        let cell_count = self.color_index.cell_count();
+        self.color_index = ColorIndex::new(self.distance_metric, cell_count);
+    }
+
+    fn select(&self, target_color: &[f32; 3], candidates: &Candidates) -> Option<usize> {
+        return self
+            .color_index
+            .nearest_matching(target_color, candidates.tie_salt(), |cell| {
+                candidates.accepts(cell)
+            });
+    }
+}
+
+// the selector chosen in config
+pub fn position_selector(
+    kind: SelectorKind,
+    distance_metric: DistanceMetric,
+    cell_count: usize,
+) -> Box<dyn PositionSelector> {
+    match kind {
+        SelectorKind::BruteForce => Box::new(BruteForceSelector),
+        SelectorKind::Indexed => Box::new(IndexedSelector::new(distance_metric, cell_count)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{claim_location, place_pixel, CellState, PaintingBuilder, Pixel};
+    use image::Rgb;
+
+    // paint a canvas with three regions to completion, checking every answer of the selector,
+    // exact selectors must also return the best scored candidate
+    fn check_conformance(
+        make_selector: impl Fn(DistanceMetric, usize) -> Box<dyn PositionSelector>,
+        exact: bool,
+    ) {
+        for distance_metric in [
+            DistanceMetric::Rgb,
+            DistanceMetric::Lab76,
+            DistanceMetric::Oklab,
+        ] {
+            let mut painter = PaintingBuilder::new(24, 24)
+                .distance(distance_metric)
+                .custom_selector(make_selector(distance_metric, 24 * 24))
+                .build()
+                .unwrap();
+            painter.region_frontier_counts = vec![0; 3];
+
+            // deterministic colors from a small LCG, coarse enough to produce exact ties
+            let mut state: u64 = 12345;
+            let mut next_color = || {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                Rgb([
+                    (state >> 56) as u8 & 0xf0,
+                    (state >> 48) as u8 & 0xf0,
+                    (state >> 40) as u8 & 0xf0,
+                ])
+            };
+            for (owner, (x, y)) in [(0, 0), (12, 12), (23, 5)].into_iter().enumerate() {
+                claim_location(y * 24 + x, owner as u32, &mut painter);
+                let target_pixel = Pixel {
+                    position: Coordinate {
+                        x: x as u32,
+                        y: y as u32,
+                    },
+                    color: next_color(),
+                };
+                place_pixel(&target_pixel, &mut painter);
+            }
+
+            let mut step: u64 = 0;
+            while !painter.boundry_region_list.is_empty() {
+                let target_color = next_color();
+                let converted_target_color = distance_metric.convert(target_color);
+                // every other color is restricted to one of the regions, if it can still grow
+                let region = Some((step % 3) as u32)
+                    .filter(|region| painter.region_frontier_counts[*region as usize] > 0)
+                    .filter(|_| step.is_multiple_of(2));
+                let candidates = Candidates {
+                    painter: &painter,
+                    region,
+                    tie_salt: step,
+                };
+                let cell = painter
+                    .position_selector
+                    .select(&converted_target_color, &candidates)
+                    .unwrap();
+                assert!(painter.occupancy[cell] == CellState::Frontier);
+                assert!(candidates.accepts(cell));
+
+                if exact {
+                    let rank = |location: &Coordinate| {
+                        let cell = candidates.cell(location);
+                        let score = candidates.score(location, &converted_target_color);
+                        (score, candidates.tie_break_key(cell), cell)
+                    };
+                    let best = candidates
+                        .frontier()
+                        .iter()
+                        .filter(|location| candidates.accepts(candidates.cell(location)))
+                        .map(rank)
+                        .min_by(|a, b| a.partial_cmp(b).unwrap())
+                        .unwrap();
+                    let location = candidates.frontier()[painter.boundry_region_positions[cell]];
+                    assert_eq!(rank(&location), best);
+                }
+
+                let target_pixel = Pixel {
+                    position: Coordinate {
+                        x: cell as u32 % 24,
+                        y: cell as u32 / 24,
+                    },
+                    color: target_color,
+                };
+                place_pixel(&target_pixel, &mut painter);
+                step += 1;
+            }
+            assert_eq!(painter.canvas_stats.current_pixels_placed_count, 24 * 24);
+        }
+    }
+
+    // a selector written outside this module, the first accepted location in frontier order
+    struct FirstCandidateSelector;
+
+    impl PositionSelector for FirstCandidateSelector {
+        fn select(&self, _target_color: &[f32; 3], candidates: &Candidates) -> Option<usize> {
+            return candidates
+                .frontier()
+                .iter()
+                .map(|location| candidates.cell(location))
+                .find(|cell| candidates.accepts(*cell));
+        }
+    }
+
+    #[test]
+    fn custom_selector_conforms() {
+        check_conformance(|_, _| Box::new(FirstCandidateSelector), false);
+    }
+
+    #[test]
+    fn brute_force_selector_conforms() {
+        check_conformance(|_, _| Box::new(BruteForceSelector), true);
+    }
+
+    #[test]
+    fn indexed_selector_conforms() {
+        check_conformance(
+            |distance_metric, cell_count| {
+                Box::new(IndexedSelector::new(distance_metric, cell_count))
+            },
+            true,
+        );
+    }
+}
@@ -0,0 +1,276 @@
+use crate::metrics::{tie_break_key, DistanceMetric};
+
+// points per leaf before the leaf is split in two
+const LEAF_CAPACITY: usize = 32;
+
+// relative slack applied to pruning bounds so float rounding never prunes an exact tie
+const BOUND_SLACK: f32 = 1e-5;
+
+// a colored neighbor of a boundry region location, stored in the metric's color space
+#[derive(Copy, Clone)]
+struct IndexedColor {
+    color: [f32; 3],
+    cell: usize,
+}
+
+enum Node {
+    Split {
+        axis: usize,
+        value: f32,
+        left: usize,
+        right: usize,
+    },
+    Leaf {
+        points: Vec<IndexedColor>,
+    },
+}
+
+// k-d tree over the colors of every colored neighbor of every boundry region location,
+// the nearest point to a target color belongs to the location with the smallest MIN score
+//
+// points are added as neighbors get painted, whole locations are removed lazily once they are
+// painted themselves and the tree is rebuilt when removed points outnumber live ones
+pub struct ColorIndex {
+    distance_metric: DistanceMetric,
+    nodes: Vec<Node>,
+    removed_cells: Vec<bool>,
+    cell_point_counts: Vec<u32>,
+    live_points: usize,
+    removed_points: usize,
+}
+
+impl ColorIndex {
+    // an empty index for a canvas with the given number of locations
+    pub fn new(distance_metric: DistanceMetric, cell_count: usize) -> ColorIndex {
+        return ColorIndex {
+            distance_metric,
+            nodes: vec![Node::Leaf { points: Vec::new() }],
+            removed_cells: vec![false; cell_count],
+            cell_point_counts: vec![0; cell_count],
+            live_points: 0,
+            removed_points: 0,
+        };
+    }
+
+    // number of locations of the canvas the index was made for
+    pub fn cell_count(&self) -> usize {
+        return self.removed_cells.len();
+    }
+
+    // only metrics where the best location is the one with the single closest neighbor can be
+    // answered by a nearest neighbor query, and pruning needs an axis aligned lower bound
+    pub fn supports(distance_metric: DistanceMetric) -> bool {
+        return distance_metric != DistanceMetric::Ciede2000;
+    }
+
+    // record a colored neighbor (already converted for the metric) of a boundry region location
+    pub fn insert(&mut self, color: [f32; 3], cell: usize) {
+        let point = IndexedColor { color, cell };
+        self.cell_point_counts[cell] += 1;
+        self.live_points += 1;
+
+        // descend to the leaf holding this color
+        let mut node_index = 0;
+        while let Node::Split {
+            axis,
+            value,
+            left,
+            right,
+        } = self.nodes[node_index]
+        {
+            node_index = if color[axis] < value { left } else { right };
+        }
+        if let Node::Leaf { points } = &mut self.nodes[node_index] {
+            points.push(point);
+            // leaves of identical colors cannot split, only retry once per LEAF_CAPACITY inserts
+            if points.len() > LEAF_CAPACITY && points.len() % LEAF_CAPACITY == 1 {
+                self.split_leaf(node_index);
+            }
+        }
+    }
+
+    // drop every point of a location that left the boundry region
+    pub fn remove(&mut self, cell: usize) {
+        if self.removed_cells[cell] {
+            return;
+        }
+        self.removed_cells[cell] = true;
+        self.live_points -= self.cell_point_counts[cell] as usize;
+        self.removed_points += self.cell_point_counts[cell] as usize;
+
+        // rebuild once most of the tree is dead weight
+        if self.removed_points > LEAF_CAPACITY && self.removed_points > self.live_points {
+            self.rebuild();
+        }
+    }
+
+    // the location holding the point nearest to the target color among the locations accepted
+    // by the filter, ties broken by tie_break_key and then by location
+    pub fn nearest_matching(
+        &self,
+        target_color: &[f32; 3],
+        tie_salt: u64,
+        filter: impl Fn(usize) -> bool,
+    ) -> Option<usize> {
+        let mut best: Option<(f32, u64, usize)> = None;
+        self.search(0, target_color, tie_salt, &filter, &mut best);
+        return best.map(|(_, _, cell)| cell);
+    }
+
+    fn search(
+        &self,
+        node_index: usize,
+        target_color: &[f32; 3],
+        tie_salt: u64,
+        filter: &impl Fn(usize) -> bool,
+        best: &mut Option<(f32, u64, usize)>,
+    ) {
+        match &self.nodes[node_index] {
+            Node::Leaf { points } => {
+                for point in points {
+                    if self.removed_cells[point.cell] || !filter(point.cell) {
+                        continue;
+                    }
+                    let distance = self.distance_metric.distance(target_color, &point.color);
+                    let key = tie_break_key(tie_salt, point.cell);
+                    let is_better = match best {
+                        Some((best_distance, best_key, best_cell)) => {
+                            distance < *best_distance
+                                || (distance == *best_distance
+                                    && (key, point.cell) < (*best_key, *best_cell))
+                        }
+                        None => true,
+                    };
+                    if is_better {
+                        *best = Some((distance, key, point.cell));
+                    }
+                }
+            }
+            Node::Split {
+                axis,
+                value,
+                left,
+                right,
+            } => {
+                // visit the side containing the target first
+                let offset = target_color[*axis] - value;
+                let (near, far) = if offset < 0f32 {
+                    (*left, *right)
+                } else {
+                    (*right, *left)
+                };
+                self.search(near, target_color, tie_salt, filter, best);
+
+                // the far side can only win (or tie) if its closest possible point is no
+                // further than the best found so far
+                let bound = self.distance_metric.axis_bound(offset) * (1f32 - BOUND_SLACK);
+                if best.is_none_or(|(best_distance, _, _)| bound <= best_distance) {
+                    self.search(far, target_color, tie_salt, filter, best);
+                }
+            }
+        }
+    }
+
+    // split an overfull leaf at the median of its widest axis
+    fn split_leaf(&mut self, node_index: usize) {
+        let points = match &mut self.nodes[node_index] {
+            Node::Leaf { points } => std::mem::take(points),
+            Node::Split { .. } => return,
+        };
+        match partition(points) {
+            Ok((axis, value, left_points, right_points)) => {
+                let left = self.nodes.len();
+                self.nodes.push(Node::Leaf {
+                    points: left_points,
+                });
+                self.nodes.push(Node::Leaf {
+                    points: right_points,
+                });
+                self.nodes[node_index] = Node::Split {
+                    axis,
+                    value,
+                    left,
+                    right: left + 1,
+                };
+            }
+            // every point has the same color, keep an oversized leaf
+            Err(points) => self.nodes[node_index] = Node::Leaf { points },
+        }
+    }
+
+    // rebuild the tree from the live points only
+    fn rebuild(&mut self) {
+        let mut live: Vec<IndexedColor> = Vec::with_capacity(self.live_points);
+        for node in self.nodes.drain(..) {
+            if let Node::Leaf { points } = node {
+                live.extend(points.into_iter().filter(|p| !self.removed_cells[p.cell]));
+            }
+        }
+        self.removed_points = 0;
+        self.nodes.push(Node::Leaf { points: Vec::new() });
+        self.build(0, live);
+    }
+
+    // fill the node at node_index with a balanced subtree over the given points
+    fn build(&mut self, node_index: usize, points: Vec<IndexedColor>) {
+        if points.len() <= LEAF_CAPACITY {
+            self.nodes[node_index] = Node::Leaf { points };
+            return;
+        }
+        match partition(points) {
+            Ok((axis, value, left_points, right_points)) => {
+                let left = self.nodes.len();
+                self.nodes.push(Node::Leaf { points: Vec::new() });
+                self.nodes.push(Node::Leaf { points: Vec::new() });
+                self.nodes[node_index] = Node::Split {
+                    axis,
+                    value,
+                    left,
+                    right: left + 1,
+                };
+                self.build(left, left_points);
+                self.build(left + 1, right_points);
+            }
+            Err(points) => self.nodes[node_index] = Node::Leaf { points },
+        }
+    }
+}
+
+// split points at the median of their widest axis into (axis, value, below, at or above),
+// both halves are non-empty, Err returns the points unchanged when they all share one color
+#[allow(clippy::type_complexity)]
+fn partition(
+    mut points: Vec<IndexedColor>,
+) -> Result<(usize, f32, Vec<IndexedColor>, Vec<IndexedColor>), Vec<IndexedColor>> {
+    // find the axis with the largest spread
+    let mut axis = 0;
+    let mut widest = 0f32;
+    for candidate in 0..3 {
+        let (min, max) = points.iter().fold((f32::MAX, f32::MIN), |(min, max), p| {
+            (min.min(p.color[candidate]), max.max(p.color[candidate]))
+        });
+        if max - min > widest {
+            widest = max - min;
+            axis = candidate;
+        }
+    }
+    if widest <= 0f32 {
+        return Err(points);
+    }
+
+    // split at the median, moving past values equal to the minimum so both sides are used
+    points.sort_by(|a, b| a.color[axis].total_cmp(&b.color[axis]));
+    let mut split = points.len() / 2;
+    if points[split].color[axis] == points[0].color[axis] {
+        split = points
+            .iter()
+            .position(|p| p.color[axis] > points[0].color[axis])
+            .unwrap();
+    }
+    while split > 0 && points[split - 1].color[axis] == points[split].color[axis] {
+        split -= 1;
+    }
+    let value = points[split].color[axis];
+    let right_points = points.split_off(split);
+    return Ok((axis, value, points, right_points));
+}